@@ -0,0 +1,298 @@
+// 目录打包下载: 边遍历边压缩，直接写入响应体，不在磁盘或内存中暂存整个压缩包
+use crate::auth::AuthUser;
+use crate::disposition::Disposition;
+use crate::walk::{Entry, Source, Walker};
+use crate::{format_size, AppState, PKG_NAME};
+use anyhow::{Context, Result};
+use async_compression::tokio::write::GzipEncoder;
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipDateTime, ZipEntryBuilder};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Form, Path, Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use tokio_util::io::ReaderStream;
+use tracing::{error, info};
+
+// 压缩任务和响应体之间的管道缓冲区大小
+const PIPE_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub fn from_query(params: &HashMap<String, String>) -> Option<Self> {
+        match params.get("format").map(String::as_str) {
+            None | Some("zip") => Some(Self::Zip),
+            Some("tar.gz") | Some("tgz") => Some(Self::TarGz),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+        }
+    }
+}
+
+// 打包下载根目录 (多个根目录时每个根目录作为压缩包中的一个文件夹)
+pub async fn archive_root(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(format) = ArchiveFormat::from_query(&params) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let (sources, name) = if let [root] = state.roots.as_slice() {
+        let name = root.alias.clone();
+        (vec![Source { path: root.path.clone(), name: name.clone() }], name)
+    } else {
+        let sources = state
+            .roots
+            .iter()
+            .map(|root| Source { path: root.path.clone(), name: root.alias.clone() })
+            .collect();
+        (sources, PKG_NAME.to_string())
+    };
+
+    info!(ip = %addr.ip(), user = %user, "Archive requested for root directory");
+    stream_archive(Walker::new(sources, &state), format, &name)
+}
+
+// 打包下载指定目录
+pub async fn archive_dir(
+    Path(path): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(format) = ArchiveFormat::from_query(&params) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let dir = match state.checked_path(&path, addr.ip()) {
+        Ok(dir) => dir,
+        Err(status) => return status.into_response(),
+    };
+    if !dir.is_dir() {
+        error!(ip = %addr.ip(), user = %user, "Archive target is not a directory: {:?}", dir);
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|n| !n.is_empty())
+        .unwrap_or(PKG_NAME)
+        .to_string();
+
+    info!(ip = %addr.ip(), user = %user, "Archive requested for: {}", path);
+    let sources = vec![Source { path: dir, name: name.clone() }];
+    stream_archive(Walker::new(sources, &state), format, &name)
+}
+
+// 打包下载勾选的多个文件/目录
+// 表单字段: path (可重复，与 /files/ 后的路径相同) 和 format
+pub async fn archive_selected(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Response {
+    let params: HashMap<String, String> = fields
+        .iter()
+        .filter(|(key, _)| key == "format")
+        .cloned()
+        .collect();
+    let Some(format) = ArchiveFormat::from_query(&params) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    let mut sources: Vec<Source> = Vec::new();
+    for (_, path) in fields.iter().filter(|(key, _)| key == "path") {
+        let full_path = match state.checked_path(path, addr.ip()) {
+            Ok(full_path) => full_path,
+            Err(status) => return status.into_response(),
+        };
+        if !full_path.exists() {
+            error!(ip = %addr.ip(), user = %user, "File not found: {:?}", full_path);
+            return StatusCode::NOT_FOUND.into_response();
+        }
+
+        // 不同目录下的同名文件在压缩包中追加序号
+        let base = path.trim_end_matches('/').rsplit('/').next().unwrap_or(path).to_string();
+        let mut name = base.clone();
+        let mut n = 2;
+        while sources.iter().any(|source| source.name == name) {
+            name = format!("{}-{}", base, n);
+            n += 1;
+        }
+        sources.push(Source { path: full_path, name });
+    }
+
+    if sources.is_empty() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    match total_size(Walker::new(sources.clone(), &state), state.select_limit).await {
+        Ok(total) if total <= state.select_limit => {
+            info!(ip = %addr.ip(), user = %user, "Archive requested for {} selected items ({} bytes)", sources.len(), total);
+        }
+        Ok(_) => {
+            error!(ip = %addr.ip(), user = %user, "Selected items exceed limit of {} bytes", state.select_limit);
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("所选文件总大小超过上限 {}", format_size(state.select_limit)),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            error!(ip = %addr.ip(), user = %user, "Failed to read selected items: {:#}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    stream_archive(Walker::new(sources, &state), format, "selected")
+}
+
+// 统计来源中所有文件的总大小，超过 limit 后提前返回
+async fn total_size(mut walker: Walker, limit: u64) -> Result<u64> {
+    let mut total = 0u64;
+    while let Some(entry) = walker.next().await? {
+        if let Entry::File { size, .. } = entry {
+            total = total.saturating_add(size);
+            if total > limit {
+                break;
+            }
+        }
+    }
+    Ok(total)
+}
+
+// 在后台任务中生成压缩包，通过管道流式返回
+// 打包出错时让响应体以错误结束，客户端会看到下载失败而不是一个被截断的压缩包
+fn stream_archive(walker: Walker, format: ArchiveFormat, name: &str) -> Response {
+    let (reader, writer) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+
+    let task = tokio::spawn(async move {
+        match format {
+            ArchiveFormat::Zip => write_zip(writer, walker).await,
+            ArchiveFormat::TarGz => write_tar_gz(writer, walker).await,
+        }
+    });
+
+    let tail = stream::once(async move {
+        let result = match task.await {
+            Ok(result) => result,
+            Err(e) => Err(e.into()),
+        };
+        result.err().map(|e| {
+            error!("Failed to build archive: {:#}", e);
+            Err(io::Error::other(e))
+        })
+    })
+    .filter_map(|item| async move { item });
+
+    let body = Body::from_stream(ReaderStream::with_capacity(reader, PIPE_BUFFER_SIZE).chain(tail));
+
+    let file_name = format!("{}.{}", name, format.extension());
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, format.content_type().parse().unwrap());
+    headers.insert(CONTENT_DISPOSITION, Disposition::Attachment.header_value(&file_name));
+
+    (StatusCode::OK, headers, body).into_response()
+}
+
+async fn write_zip(writer: DuplexStream, mut walker: Walker) -> Result<()> {
+    let mut zip = ZipFileWriter::with_tokio(writer);
+
+    while let Some(entry) = walker.next().await? {
+        match entry {
+            Entry::Dir { name, modified } => {
+                let builder = zip_entry(format!("{}/", name), Compression::Stored, modified);
+                zip.write_entry_whole(builder, &[]).await?;
+            }
+            Entry::File { path, name, modified, .. } => {
+                let mut file = File::open(&path)
+                    .await
+                    .with_context(|| format!("Failed to open file {:?}", path))?
+                    .compat();
+                let builder = zip_entry(name, Compression::Deflate, modified);
+                let mut entry_writer = zip.write_entry_stream(builder).await?;
+                futures_util::io::copy(&mut file, &mut entry_writer)
+                    .await
+                    .with_context(|| format!("Failed to read file {:?}", path))?;
+                entry_writer.close().await?;
+            }
+        }
+    }
+
+    zip.close().await?.into_inner().shutdown().await?;
+    Ok(())
+}
+
+fn zip_entry(name: String, compression: Compression, modified: Option<DateTime<Utc>>) -> ZipEntryBuilder {
+    let builder = ZipEntryBuilder::new(name.into(), compression);
+    match modified {
+        Some(modified) => builder.last_modification_date(ZipDateTime::from_chrono(&modified)),
+        None => builder,
+    }
+}
+
+async fn write_tar_gz(writer: DuplexStream, mut walker: Walker) -> Result<()> {
+    let mut tar = tokio_tar::Builder::new(GzipEncoder::new(writer));
+
+    while let Some(entry) = walker.next().await? {
+        match entry {
+            Entry::Dir { name, modified } => {
+                let mut header = tokio_tar::Header::new_gnu();
+                header.set_entry_type(tokio_tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                if let Some(modified) = modified {
+                    header.set_mtime(modified.timestamp().max(0) as u64);
+                }
+                tar.append_data(&mut header, format!("{}/", name), tokio::io::empty()).await?;
+            }
+            Entry::File { path, name, .. } => {
+                tar.append_path_with_name(&path, &name)
+                    .await
+                    .with_context(|| format!("Failed to add file {:?}", path))?;
+            }
+        }
+    }
+
+    let mut encoder = tar.into_inner().await?;
+    encoder.shutdown().await?;
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use crate::AppState;
+use anyhow::{anyhow, Context, Result};
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE, SET_COOKIE, WWW_AUTHENTICATE},
+        request::Parts,
+        HeaderMap, StatusCode, Uri,
+    },
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use moka::future::Cache;
+use rand::{distr::Alphanumeric, Rng};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use tracing::error;
+
+// 保存令牌的 cookie 名
+const TOKEN_COOKIE: &str = "share_these_token";
+
+// 随机令牌长度
+const RANDOM_TOKEN_LEN: usize = 32;
+
+// 已验证的 Basic 凭据缓存时间，避免每个请求都重新计算 bcrypt/argon2
+const VERIFIED_TTL: Duration = Duration::from_secs(300);
+
+// 生成随机访问令牌
+pub fn generate_token() -> String {
+    rand::rng()
+        .sample_iter(Alphanumeric)
+        .take(RANDOM_TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+// 从 htpasswd 风格文件加载的用户列表 (user:hash，支持 bcrypt 和 argon2)
+pub struct UserStore {
+    users: HashMap<String, String>,
+    // Authorization 头 -> 用户名
+    verified: Cache<String, String>,
+}
+
+impl UserStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read auth file {:?}", path))?;
+        Self::parse(&content).with_context(|| format!("Invalid auth file {:?}", path))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut users = HashMap::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (user, hash) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Invalid auth file line {}: expected user:hash", idx + 1))?;
+            if !is_bcrypt(hash) && !hash.starts_with("$argon2") {
+                return Err(anyhow!(
+                    "Unsupported hash for user '{}' on line {}: only bcrypt and argon2 are supported",
+                    user,
+                    idx + 1
+                ));
+            }
+            users.insert(user.to_string(), hash.to_string());
+        }
+
+        if users.is_empty() {
+            return Err(anyhow!("No users found"));
+        }
+
+        Ok(Self {
+            users,
+            verified: Cache::builder().time_to_live(VERIFIED_TTL).build(),
+        })
+    }
+
+    pub fn user_count(&self) -> usize {
+        self.users.len()
+    }
+
+    // 校验 "Basic xxx" 凭据，成功时返回用户名
+    async fn verify(&self, header: &str) -> Option<String> {
+        if let Some(user) = self.verified.get(header).await {
+            return Some(user);
+        }
+
+        let encoded = header.strip_prefix("Basic ")?.trim();
+        let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        let hash = self.users.get(user)?.clone();
+
+        // 密码哈希校验很耗 CPU，放到阻塞线程池中执行
+        let password = password.to_string();
+        let ok = tokio::task::spawn_blocking(move || verify_password(&password, &hash))
+            .await
+            .unwrap_or(false);
+        if !ok {
+            return None;
+        }
+
+        self.verified.insert(header.to_string(), user.to_string()).await;
+        Some(user.to_string())
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|p| hash.starts_with(p))
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    if is_bcrypt(hash) {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    } else {
+        PasswordHash::new(hash)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false)
+    }
+}
+
+// 当前请求的认证用户，未启用 Basic 认证时为 "-"
+#[derive(Clone)]
+pub struct AuthUser(pub Option<String>);
+
+impl fmt::Display for AuthUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_deref().unwrap_or("-"))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<AuthUser>().cloned().unwrap_or(AuthUser(None)))
+    }
+}
+
+// 访问控制中间件: 未通过认证的请求直接返回 401
+// Basic 认证使用 --auth-file 中的账号；
+// 令牌可以来自查询参数 ?token=、cookie 或 Authorization: Bearer 头；
+// 两者都启用时都需要通过: 令牌不能代替账号，日志始终能记录是谁访问的
+// (此时 Authorization 头用于 Basic 认证，令牌只能通过 ?token= 或 cookie 传递)
+pub async fn require_auth(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if state.token.is_none() && state.users.is_none() {
+        return next.run(request).await;
+    }
+
+    let access = authenticate(state.token.as_deref(), state.users.as_deref(), request.uri(), request.headers()).await;
+    let Some(access) = access else {
+        return unauthorized(&state, &request);
+    };
+    request.extensions_mut().insert(AuthUser(access.user));
+
+    let mut response = next.run(request).await;
+    // 查询参数中的令牌验证通过后写入 cookie，后续页面跳转无需再带参数
+    if let (true, Some(token)) = (access.token_from_query, state.token.as_deref()) {
+        let cookie = format!("{}={}; Path=/; HttpOnly; SameSite=Lax", TOKEN_COOKIE, token);
+        if let Ok(value) = cookie.parse() {
+            response.headers_mut().append(SET_COOKIE, value);
+        }
+    }
+    response
+}
+
+// 通过认证的请求
+struct Access {
+    // Basic 认证的用户名
+    user: Option<String>,
+    // 令牌来自 ?token=，需要写入 cookie
+    token_from_query: bool,
+}
+
+// 检查请求的凭据，未通过时返回 None
+async fn authenticate(token: Option<&str>, users: Option<&UserStore>, uri: &Uri, headers: &HeaderMap) -> Option<Access> {
+    let authorization = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+
+    let user = match users {
+        Some(users) => Some(users.verify(authorization?).await?),
+        None => None,
+    };
+
+    let Some(token) = token else {
+        return Some(Access { user, token_from_query: false });
+    };
+
+    let from_query = Query::<HashMap<String, String>>::try_from_uri(uri)
+        .ok()
+        .and_then(|Query(params)| params.get("token").cloned());
+    if from_query.as_deref().is_some_and(|t| constant_time_eq(t, token)) {
+        return Some(Access { user, token_from_query: true });
+    }
+
+    let from_bearer = authorization.and_then(|v| v.strip_prefix("Bearer ")).map(str::trim);
+    let from_cookie = headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == TOKEN_COOKIE)
+        .map(|(_, value)| value);
+
+    from_bearer
+        .into_iter()
+        .chain(from_cookie)
+        .any(|t| constant_time_eq(t, token))
+        .then_some(Access { user, token_from_query: false })
+}
+
+// 401 响应，启用 Basic 认证时让浏览器弹出账号输入框
+fn unauthorized(state: &AppState, request: &Request) -> Response {
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_default();
+    error!(ip = %ip, "Unauthorized request: {}", request.uri().path());
+
+    let mut response = (
+        StatusCode::UNAUTHORIZED,
+        Html(r#"<html><body><h1>401 Unauthorized</h1><p>缺少或无效的访问凭据</p></body></html>"#),
+    )
+        .into_response();
+    // 让浏览器弹出用户名/密码输入框
+    if state.users.is_some() {
+        response.headers_mut().insert(
+            WWW_AUTHENTICATE,
+            r#"Basic realm="share_these", charset="UTF-8""#.parse().unwrap(),
+        );
+    }
+    response
+}
+
+// 常量时间比较，避免通过响应时间猜测令牌
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // alice 的密码为 pw (bcrypt)，bob 的密码为 pw2 (argon2id)
+    const USERS: &str = "# 注释和空行被忽略\n\nalice:$2y$04$6g1ItD/bUKv1.f68EsrfbuClrEZioKvMQW0XUsUKbk.JfKIXg19bC\nbob:$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHRzb21lc2FsdA$wc8mWaN9GcJFF4O+MXKHCgW6pOYRKD+LbemDRxs5MO8\n";
+
+    fn basic(user: &str, password: &str) -> String {
+        format!("Basic {}", STANDARD.encode(format!("{}:{}", user, password)))
+    }
+
+    fn headers(pairs: &[(axum::http::HeaderName, &str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.clone(), value.parse().unwrap())).collect()
+    }
+
+    async fn check(token: Option<&str>, users: Option<&UserStore>, uri: &str, headers: &HeaderMap) -> Option<Access> {
+        authenticate(token, users, &uri.parse().unwrap(), headers).await
+    }
+
+    #[test]
+    fn parse_users() {
+        let store = UserStore::parse(USERS).unwrap();
+        assert_eq!(store.user_count(), 2);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_files() {
+        assert!(UserStore::parse("alice").is_err());
+        assert!(UserStore::parse("alice:plaintext").is_err());
+        // 不支持 MD5 (apr1) 和 SHA1
+        assert!(UserStore::parse("alice:$apr1$salt$hash").is_err());
+        assert!(UserStore::parse("alice:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=").is_err());
+        assert!(UserStore::parse("# only comments\n\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_bcrypt_and_argon2() {
+        let store = UserStore::parse(USERS).unwrap();
+        assert_eq!(store.verify(&basic("alice", "pw")).await.as_deref(), Some("alice"));
+        assert_eq!(store.verify(&basic("bob", "pw2")).await.as_deref(), Some("bob"));
+        assert_eq!(store.verify(&basic("alice", "wrong")).await, None);
+        assert_eq!(store.verify(&basic("carol", "pw")).await, None);
+        assert_eq!(store.verify("Bearer pw").await, None);
+    }
+
+    #[tokio::test]
+    async fn token_only() {
+        let token = Some("secret");
+        let access = check(token, None, "/?token=secret", &HeaderMap::new()).await.unwrap();
+        assert!(access.token_from_query && access.user.is_none());
+        assert!(check(token, None, "/", &headers(&[(AUTHORIZATION, "Bearer secret")])).await.is_some());
+        assert!(check(token, None, "/", &headers(&[(COOKIE, "a=b; share_these_token=secret")])).await.is_some());
+        assert!(check(token, None, "/?token=wrong", &HeaderMap::new()).await.is_none());
+        assert!(check(token, None, "/", &HeaderMap::new()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn token_and_basic_both_required() {
+        let store = UserStore::parse(USERS).unwrap();
+        let (token, users) = (Some("secret"), Some(&store));
+        let alice = basic("alice", "pw");
+
+        // 令牌不能代替账号
+        assert!(check(token, users, "/?token=secret", &HeaderMap::new()).await.is_none());
+        assert!(check(token, users, "/", &headers(&[(AUTHORIZATION, "Bearer secret")])).await.is_none());
+        // 账号也不能代替令牌
+        assert!(check(token, users, "/", &headers(&[(AUTHORIZATION, &alice)])).await.is_none());
+
+        let access = check(token, users, "/?token=secret", &headers(&[(AUTHORIZATION, &alice)])).await.unwrap();
+        assert_eq!(access.user.as_deref(), Some("alice"));
+        let cookie = headers(&[(AUTHORIZATION, &alice), (COOKIE, "share_these_token=secret")]);
+        assert_eq!(check(token, users, "/", &cookie).await.unwrap().user.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn basic_only() {
+        let store = UserStore::parse(USERS).unwrap();
+        let access = check(None, Some(&store), "/", &headers(&[(AUTHORIZATION, &basic("bob", "pw2"))])).await;
+        assert_eq!(access.unwrap().user.as_deref(), Some("bob"));
+        assert!(check(None, Some(&store), "/", &HeaderMap::new()).await.is_none());
+    }
+}
@@ -0,0 +1,159 @@
+// 小文件内存缓存: 按字节数计算容量，并统计命中/未命中/淘汰次数
+use crate::conditional::Validators;
+use axum::body::Bytes;
+use axum::body::Body;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use moka::future::Cache;
+use moka::notification::RemovalCause;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
+
+// 缓存的小文件: 内容用引用计数的 Bytes 共享，响应头在写入缓存时预先生成，命中时无需复制
+// 验证器记录读取时的修改时间，与内容长度一起用于判断磁盘上的文件是否已变化
+pub struct CachedFile {
+    pub body: Bytes,
+    pub headers: HeaderMap,
+    pub validators: Validators,
+}
+
+impl CachedFile {
+    pub fn is_fresh(&self, metadata: &std::fs::Metadata) -> bool {
+        metadata.is_file()
+            && metadata.len() == self.body.len() as u64
+            && metadata.modified().ok() == self.validators.last_modified
+    }
+
+    // 命中时只复制引用计数和少量响应头，不复制文件内容
+    pub fn response(&self) -> Response {
+        (StatusCode::OK, self.headers.clone(), Body::from(self.body.clone())).into_response()
+    }
+}
+
+#[derive(Default)]
+pub struct CacheStats {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    pub evictions: AtomicU64,
+    pub invalidations: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct FileCache {
+    // 禁用缓存时为 None
+    inner: Option<Cache<String, Arc<CachedFile>>>,
+    max_file_size: u64,
+    stats: Arc<CacheStats>,
+}
+
+impl FileCache {
+    // max_bytes 或 max_file_size 为 0 时禁用缓存
+    pub fn new(max_bytes: u64, max_file_size: u64, ttl: Option<Duration>) -> Self {
+        let stats = Arc::new(CacheStats::default());
+        if max_bytes == 0 || max_file_size == 0 {
+            return Self { inner: None, max_file_size: 0, stats };
+        }
+
+        let listener_stats = stats.clone();
+        let mut builder = Cache::builder()
+            .max_capacity(max_bytes)
+            // 按键和内容的字节数计算权重
+            .weigher(|key: &String, value: &Arc<CachedFile>| {
+                u32::try_from(key.len() + value.body.len()).unwrap_or(u32::MAX)
+            })
+            .eviction_listener(move |key, value: Arc<CachedFile>, cause| {
+                if matches!(cause, RemovalCause::Size | RemovalCause::Expired) {
+                    listener_stats.evictions.fetch_add(1, Ordering::Relaxed);
+                    debug!("Cache evicted ({:?}): {} ({} bytes)", cause, key, value.body.len());
+                }
+            });
+        if let Some(ttl) = ttl {
+            builder = builder.time_to_live(ttl);
+        }
+
+        Self {
+            inner: Some(builder.build()),
+            max_file_size,
+            stats,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    // 文件是否小到可以放进缓存
+    pub fn accepts(&self, size: u64) -> bool {
+        self.inner.is_some() && size <= self.max_file_size
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Arc<CachedFile>> {
+        let cached = self.inner.as_ref()?.get(key).await;
+        let counter = if cached.is_some() { &self.stats.hits } else { &self.stats.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    pub async fn insert(&self, key: String, value: Arc<CachedFile>) {
+        if let Some(cache) = &self.inner {
+            cache.insert(key, value).await;
+        }
+    }
+
+    // 文件在磁盘上已变化，丢弃缓存
+    pub async fn invalidate(&self, key: &str) {
+        if let Some(cache) = &self.inner {
+            self.stats.invalidations.fetch_add(1, Ordering::Relaxed);
+            cache.invalidate(key).await;
+        }
+    }
+
+    pub fn stats(&self) -> &CacheStats {
+        &self.stats
+    }
+
+    // 当前缓存的条目数和占用字节数
+    pub async fn usage(&self) -> (u64, u64) {
+        match &self.inner {
+            Some(cache) => {
+                cache.run_pending_tasks().await;
+                (cache.entry_count(), cache.weighted_size())
+            }
+            None => (0, 0),
+        }
+    }
+
+    // 定期输出缓存统计，统计没有变化时不输出
+    pub fn spawn_stats_logger(&self, interval: Duration) {
+        if !self.is_enabled() {
+            return;
+        }
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let mut last = (0, 0, 0, 0);
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let stats = cache.stats();
+                let current = (
+                    stats.hits.load(Ordering::Relaxed),
+                    stats.misses.load(Ordering::Relaxed),
+                    stats.evictions.load(Ordering::Relaxed),
+                    stats.invalidations.load(Ordering::Relaxed),
+                );
+                if current == last {
+                    continue;
+                }
+                last = current;
+                let (entries, bytes) = cache.usage().await;
+                info!(
+                    "Cache stats: hits={} misses={} evictions={} invalidations={} entries={} bytes={}",
+                    current.0, current.1, current.2, current.3, entries, bytes
+                );
+            }
+        });
+    }
+}
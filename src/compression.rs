@@ -0,0 +1,132 @@
+// 响应压缩: 按 Accept-Encoding 动态压缩文本类内容，并优先发送预压缩的 .br / .gz 同名文件
+use axum::{
+    body::HttpBody,
+    http::{
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, ETAG},
+        HeaderMap, HeaderValue, Response,
+    },
+};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tower_http::compression::{
+    predicate::{Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+// 小于该大小的响应压缩后收益不大
+const MIN_COMPRESS_SIZE: u16 = 256;
+
+// 预压缩文件的扩展名和对应的 Content-Encoding，按优先级排列
+const PRECOMPRESSED: [(&str, &str); 2] = [("br", "br"), ("gz", "gzip")];
+
+// 值得压缩的 MIME 类型: 文本以及 JSON / JS / XML / SVG / WebAssembly
+pub fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    essence.starts_with("text/")
+        || matches!(
+            essence,
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "image/svg+xml"
+                | "application/wasm"
+        )
+}
+
+// 只压缩可压缩类型的响应
+#[derive(Clone, Copy)]
+struct CompressibleType;
+
+impl Predicate for CompressibleType {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(is_compressible)
+    }
+}
+
+// 带 Content-Range 的部分响应不会被压缩，Range 请求按原始字节返回
+pub fn layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .br(true)
+        .gzip(true)
+        .deflate(true)
+        .compress_when(SizeAbove::new(MIN_COMPRESS_SIZE).and(CompressibleType))
+}
+
+// 压缩后的内容与原文件字节不同，强 ETag 改为弱 ETag；If-None-Match 按弱比较仍然有效
+// 原文件的摘要也不再适用于压缩后的内容，直接移除
+pub async fn adjust_encoded<B>(mut response: Response<B>) -> Response<B> {
+    if !response.headers().contains_key(CONTENT_ENCODING) {
+        return response;
+    }
+    response.headers_mut().remove("repr-digest");
+    response.headers_mut().remove("digest");
+    let weak = response
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|etag| !etag.starts_with("W/"))
+        .and_then(|etag| HeaderValue::from_str(&format!("W/{}", etag)).ok());
+    if let Some(weak) = weak {
+        response.headers_mut().insert(ETAG, weak);
+    }
+    response
+}
+
+// 客户端是否接受某种编码 (q=0 表示拒绝)
+fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    let Some(accept) = headers.get(ACCEPT_ENCODING).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let mut wildcard = false;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            return quality > 0.0;
+        }
+        if name == "*" {
+            wildcard = quality > 0.0;
+        }
+    }
+    wildcard
+}
+
+// 查找客户端接受的预压缩文件 (foo.txt.br / foo.txt.gz)，比原文件旧的视为过期
+// permits 不允许的 (经过不允许的符号链接或被过滤) 跳过
+pub async fn precompressed(
+    path: &Path,
+    headers: &HeaderMap,
+    permits: impl Fn(&Path) -> bool,
+) -> Option<(PathBuf, &'static str)> {
+    let original = fs::metadata(path).await.ok()?.modified().ok()?;
+    for (extension, encoding) in PRECOMPRESSED {
+        if !accepts_encoding(headers, encoding) {
+            continue;
+        }
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(extension);
+        let sidecar = PathBuf::from(sidecar);
+        if !permits(&sidecar) {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(&sidecar).await else {
+            continue;
+        };
+        if metadata.is_file() && metadata.modified().is_ok_and(|m| m >= original) {
+            return Some((sidecar, encoding));
+        }
+    }
+    None
+}
@@ -0,0 +1,249 @@
+// 条件请求: ETag / Last-Modified 验证器，以及 If-None-Match / If-Modified-Since / If-Range 的判断
+use anyhow::{Context, Result};
+use axum::{
+    http::{
+        header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use clap::ValueEnum;
+use moka::future::Cache;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+// 内容哈希 ETag 最多缓存的文件数
+const CONTENT_ETAG_CACHE_SIZE: u64 = 10_000;
+
+// ETag 的生成方式
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EtagMode {
+    /// 由文件大小、修改时间和 inode 生成，开销最小
+    Metadata,
+    /// 由文件内容的 BLAKE3 哈希生成，首次请求需要读取整个文件
+    Content,
+}
+
+// ETag 生成器，内容哈希按 (路径, 大小, 修改时间) 缓存
+#[derive(Clone)]
+pub struct Etags {
+    mode: EtagMode,
+    content_cache: Cache<(PathBuf, u64, SystemTime), String>,
+}
+
+impl Etags {
+    pub fn new(mode: EtagMode) -> Self {
+        Self {
+            mode,
+            content_cache: Cache::new(CONTENT_ETAG_CACHE_SIZE),
+        }
+    }
+
+    pub async fn validators(&self, path: &Path, metadata: &Metadata) -> Result<Validators> {
+        let last_modified = metadata.modified().ok();
+        let etag = match (self.mode, last_modified) {
+            (EtagMode::Content, Some(modified)) => {
+                let key = (path.to_path_buf(), metadata.len(), modified);
+                self.content_cache
+                    .try_get_with(key, content_etag(path))
+                    .await
+                    .map_err(|e| anyhow::anyhow!("{:#}", e))?
+            }
+            _ => metadata_etag(metadata),
+        };
+        Ok(Validators { etag, last_modified })
+    }
+}
+
+// 由元数据生成的强 ETag: "inode-大小-修改时间(纳秒)"
+fn metadata_etag(metadata: &Metadata) -> String {
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+    #[cfg(not(unix))]
+    let inode = 0u64;
+
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("\"{:x}-{:x}-{:x}\"", inode, metadata.len(), mtime)
+}
+
+// 流式计算文件内容的 BLAKE3 哈希作为 ETag
+async fn content_etag(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("Failed to open file {:?}", path))?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read file {:?}", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    // 截取前 128 位就足够区分版本
+    Ok(format!("\"{}\"", &hasher.finalize().to_hex()[..32]))
+}
+
+// 一个文件版本的验证器
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    // 写入 ETag 和 Last-Modified 响应头
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(value) = self.etag.parse() {
+            headers.insert(ETAG, value);
+        }
+        if let Some(modified) = self.last_modified {
+            if let Ok(value) = httpdate::fmt_http_date(modified).parse() {
+                headers.insert(LAST_MODIFIED, value);
+            }
+        }
+    }
+
+    // RFC 9110 13.2.2: 有 If-None-Match 时忽略 If-Modified-Since
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+            return if_none_match.trim() == "*"
+                || if_none_match
+                    .split(',')
+                    .any(|tag| weak_eq(tag.trim(), &self.etag));
+        }
+
+        match (
+            headers
+                .get(IF_MODIFIED_SINCE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| httpdate::parse_http_date(v).ok()),
+            self.last_modified,
+        ) {
+            (Some(since), Some(modified)) => truncate_to_secs(modified) <= since,
+            _ => false,
+        }
+    }
+
+    // If-Range 与当前版本一致时才按 Range 返回部分内容，否则返回完整文件
+    // ETag 需要强比较，日期需要与 Last-Modified 完全相同
+    pub fn if_range_matches(&self, headers: &HeaderMap) -> bool {
+        let Some(if_range) = headers.get(IF_RANGE).and_then(|v| v.to_str().ok()) else {
+            return true;
+        };
+        let if_range = if_range.trim();
+
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return !if_range.starts_with("W/") && if_range == self.etag;
+        }
+
+        match (httpdate::parse_http_date(if_range).ok(), self.last_modified) {
+            (Some(date), Some(modified)) => truncate_to_secs(modified) == date,
+            _ => false,
+        }
+    }
+
+    pub fn not_modified(&self) -> Response {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        self.apply(response.headers_mut());
+        response
+    }
+}
+
+// 弱比较: 忽略 W/ 前缀
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+// HTTP 日期只精确到秒
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| UNIX_EPOCH + std::time::Duration::from_secs(d.as_secs()))
+        .unwrap_or(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use std::time::Duration;
+
+    // 2024-01-01 00:00:00 UTC，带亚秒部分以验证按秒截断
+    fn modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(1_704_067_200_500)
+    }
+
+    fn validators() -> Validators {
+        Validators { etag: "\"abc\"".to_string(), last_modified: Some(modified()) }
+    }
+
+    fn headers(pairs: &[(axum::http::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    fn http_date(time: SystemTime) -> String {
+        httpdate::fmt_http_date(time)
+    }
+
+    #[test]
+    fn if_none_match() {
+        let v = validators();
+        assert!(v.is_not_modified(&headers(&[(IF_NONE_MATCH, "\"abc\"")])));
+        assert!(v.is_not_modified(&headers(&[(IF_NONE_MATCH, "\"x\", W/\"abc\"")])));
+        assert!(v.is_not_modified(&headers(&[(IF_NONE_MATCH, "*")])));
+        assert!(!v.is_not_modified(&headers(&[(IF_NONE_MATCH, "\"other\"")])));
+        assert!(!v.is_not_modified(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let v = validators();
+        let later = http_date(modified() + Duration::from_secs(60));
+        assert!(!v.is_not_modified(&headers(&[(IF_NONE_MATCH, "\"other\""), (IF_MODIFIED_SINCE, &later)])));
+        assert!(v.is_not_modified(&headers(&[(IF_MODIFIED_SINCE, &later)])));
+    }
+
+    #[test]
+    fn if_modified_since() {
+        let v = validators();
+        assert!(v.is_not_modified(&headers(&[(IF_MODIFIED_SINCE, &http_date(modified()))])));
+        let earlier = http_date(modified() - Duration::from_secs(60));
+        assert!(!v.is_not_modified(&headers(&[(IF_MODIFIED_SINCE, &earlier)])));
+        assert!(!v.is_not_modified(&headers(&[(IF_MODIFIED_SINCE, "not a date")])));
+    }
+
+    #[test]
+    fn if_range_etag_uses_strong_comparison() {
+        let v = validators();
+        assert!(v.if_range_matches(&HeaderMap::new()));
+        assert!(v.if_range_matches(&headers(&[(IF_RANGE, "\"abc\"")])));
+        assert!(!v.if_range_matches(&headers(&[(IF_RANGE, "W/\"abc\"")])));
+        assert!(!v.if_range_matches(&headers(&[(IF_RANGE, "\"other\"")])));
+
+        let weak = Validators { etag: "W/\"abc\"".to_string(), last_modified: None };
+        assert!(!weak.if_range_matches(&headers(&[(IF_RANGE, "W/\"abc\"")])));
+    }
+
+    #[test]
+    fn if_range_date_must_equal_last_modified() {
+        let v = validators();
+        assert!(v.if_range_matches(&headers(&[(IF_RANGE, &http_date(modified()))])));
+        let older = http_date(modified() - Duration::from_secs(1));
+        assert!(!v.if_range_matches(&headers(&[(IF_RANGE, &older)])));
+        let newer = http_date(modified() + Duration::from_secs(1));
+        assert!(!v.if_range_matches(&headers(&[(IF_RANGE, &newer)])));
+    }
+}
@@ -0,0 +1,292 @@
+// 文件内容摘要: ?hash=sha256|blake3|md5 返回文件的摘要，目录返回 SHA256SUMS 格式的清单
+// 摘要按 (路径, 大小, 修改时间) 缓存，已知 SHA-256 的完整下载带上 Repr-Digest / Digest 响应头
+// 大文件的第一次完整下载不带摘要 (不为此推迟响应)，摘要在后台计算，之后的下载才会带上
+use crate::auth::AuthUser;
+use crate::disposition::Disposition;
+use crate::listing;
+use crate::walk::{self, Source, Walker};
+use crate::AppState;
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    http::{header::CONTENT_DISPOSITION, header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::stream;
+use md5::Md5;
+use moka::future::Cache;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tracing::{error, info, warn};
+
+// 最多缓存的摘要数
+const DIGEST_CACHE_SIZE: u64 = 10_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Sha256,
+    Blake3,
+    Md5,
+}
+
+impl Algorithm {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "sha256" | "sha-256" => Some(Algorithm::Sha256),
+            "blake3" => Some(Algorithm::Blake3),
+            "md5" => Some(Algorithm::Md5),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Md5 => "md5",
+        }
+    }
+
+    // 目录清单的文件名，与 sha256sum / b3sum / md5sum 的习惯一致
+    fn sums_file_name(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "SHA256SUMS",
+            Algorithm::Blake3 => "B3SUMS",
+            Algorithm::Md5 => "MD5SUMS",
+        }
+    }
+}
+
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Md5(Md5),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Hasher::Md5(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+// 摘要计算器，按 (路径, 大小, 修改时间, 算法) 缓存结果
+#[derive(Clone)]
+pub struct Digests {
+    cache: Cache<(PathBuf, u64, SystemTime, Algorithm), Vec<u8>>,
+}
+
+impl Default for Digests {
+    fn default() -> Self {
+        Self { cache: Cache::new(DIGEST_CACHE_SIZE) }
+    }
+}
+
+impl Digests {
+    // 无法取得修改时间时不缓存
+    fn key(path: &Path, metadata: &Metadata, algorithm: Algorithm) -> Option<(PathBuf, u64, SystemTime, Algorithm)> {
+        Some((path.to_path_buf(), metadata.len(), metadata.modified().ok()?, algorithm))
+    }
+
+    // 流式读取文件计算摘要
+    pub async fn get(&self, path: &Path, metadata: &Metadata, algorithm: Algorithm) -> Result<Vec<u8>> {
+        match Self::key(path, metadata, algorithm) {
+            Some(key) => self
+                .cache
+                .try_get_with(key, hash_file(path, algorithm))
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e)),
+            None => hash_file(path, algorithm).await,
+        }
+    }
+
+    // 只查询缓存，不读取文件
+    pub async fn cached(&self, path: &Path, metadata: &Metadata, algorithm: Algorithm) -> Option<Vec<u8>> {
+        self.cache.get(&Self::key(path, metadata, algorithm)?).await
+    }
+
+    // 在后台计算并缓存摘要，同一文件同时只计算一次
+    pub fn spawn(&self, path: &Path, metadata: &Metadata, algorithm: Algorithm) {
+        let (digests, path, metadata) = (self.clone(), path.to_path_buf(), metadata.clone());
+        tokio::spawn(async move {
+            if let Err(e) = digests.get(&path, &metadata, algorithm).await {
+                warn!("Failed to hash {:?}: {:#}", path, e);
+            }
+        });
+    }
+
+    // 内容已在内存中 (如读入文件缓存的小文件) 时直接计算
+    pub async fn of_bytes(&self, path: &Path, metadata: &Metadata, algorithm: Algorithm, data: &[u8]) -> Vec<u8> {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(data);
+        let digest = hasher.finalize();
+        if let Some(key) = Self::key(path, metadata, algorithm) {
+            self.cache.insert(key, digest.clone()).await;
+        }
+        digest
+    }
+}
+
+async fn hash_file(path: &Path, algorithm: Algorithm) -> Result<Vec<u8>> {
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("Failed to open file {:?}", path))?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buffer)
+            .await
+            .with_context(|| format!("Failed to read file {:?}", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 完整内容的 SHA-256: Repr-Digest (RFC 9530) 和旧客户端使用的 Digest (RFC 3230)
+// 压缩后的响应不再适用，由 compression::adjust_encoded 移除
+pub fn apply_sha256(digest: &[u8], headers: &mut HeaderMap) {
+    let encoded = STANDARD.encode(digest);
+    if let Ok(value) = HeaderValue::from_str(&format!("sha-256=:{}:", encoded)) {
+        headers.insert("repr-digest", value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!("SHA-256={}", encoded)) {
+        headers.insert("digest", value);
+    }
+}
+
+#[derive(Serialize)]
+struct FileDigest<'a> {
+    path: &'a str,
+    algorithm: &'static str,
+    // 十六进制
+    digest: String,
+    size: u64,
+}
+
+// 处理 ?hash= 请求: 文件返回 "摘要  文件名" (可直接用于 sha256sum -c)，JSON 请求返回 {path, algorithm, digest, size}
+// 目录递归列出所有文件的摘要，路径相对于该目录
+pub async fn serve(
+    full_path: &Path,
+    path: &str,
+    query: &HashMap<String, String>,
+    headers: &HeaderMap,
+    client_ip: String,
+    user: &AuthUser,
+    state: &AppState,
+) -> Response {
+    let Some(algorithm) = query.get("hash").and_then(|value| Algorithm::parse(value)) else {
+        return (StatusCode::BAD_REQUEST, "不支持的哈希算法, 可用: sha256, blake3, md5").into_response();
+    };
+
+    if full_path.is_dir() {
+        info!(ip = %client_ip, user = %user, "{} requested for: {}", algorithm.sums_file_name(), path);
+        return sums(full_path.to_path_buf(), algorithm, query, state);
+    }
+
+    let metadata = match tokio::fs::metadata(full_path).await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            error!(ip = %client_ip, user = %user, "Failed to get metadata for {:?}: {}", full_path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let digest = match state.digests.get(full_path, &metadata, algorithm).await {
+        Ok(digest) => to_hex(&digest),
+        Err(e) => {
+            error!(ip = %client_ip, user = %user, "Failed to hash {:?}: {:#}", full_path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    info!(ip = %client_ip, user = %user, "Digest ({}) requested for: {}", algorithm.as_str(), path);
+
+    if listing::wants_json(headers, query) {
+        return Json(FileDigest { path, algorithm: algorithm.as_str(), digest, size: metadata.len() }).into_response();
+    }
+    let file_name = full_path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    (
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!("{}  {}\n", digest, file_name),
+    )
+        .into_response()
+}
+
+// 目录中所有文件的摘要清单，边计算边返回
+// 遍历规则与打包下载相同，隐藏和被排除的文件不在清单中
+fn sums(dir: PathBuf, algorithm: Algorithm, query: &HashMap<String, String>, state: &AppState) -> Response {
+    let walker = Walker::new(vec![Source { path: dir, name: String::new() }], state);
+    let digests = state.digests.clone();
+    let lines = stream::unfold(Some(walker), move |walker| {
+        let digests = digests.clone();
+        async move {
+            let mut walker = walker?;
+            loop {
+                let line = match walker.next().await {
+                    Ok(Some(walk::Entry::File { path, name, .. })) => {
+                        let digest = match tokio::fs::metadata(&path).await {
+                            Ok(metadata) => digests.get(&path, &metadata, algorithm).await,
+                            Err(e) => Err(e.into()),
+                        };
+                        digest.map(|digest| format!("{}  {}\n", to_hex(&digest), name))
+                    }
+                    Ok(Some(walk::Entry::Dir { .. })) => continue,
+                    Ok(None) => return None,
+                    Err(e) => Err(e),
+                };
+                // 出错时让响应体以错误结束，避免客户端拿到不完整的清单
+                return Some(match line {
+                    Ok(line) => (Ok(line), Some(walker)),
+                    Err(e) => {
+                        error!("Failed to build {}: {:#}", algorithm.sums_file_name(), e);
+                        (Err(io::Error::other(e)), None)
+                    }
+                });
+            }
+        }
+    });
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+    headers.insert(
+        CONTENT_DISPOSITION,
+        Disposition::from_query(query).header_value(algorithm.sums_file_name()),
+    );
+    (headers, Body::from_stream(lines)).into_response()
+}
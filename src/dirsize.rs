@@ -0,0 +1,146 @@
+// 文件夹的递归大小和文件数: 列表时按需统计并缓存
+// 每次列表只等待 --dir-size-budget，没统计完的文件夹在后台继续，之后的访问直接使用缓存
+use crate::listing::{Entry, EntryKind};
+use crate::walk::{self, Source, Walker};
+use crate::AppState;
+use futures_util::future::{self, BoxFuture, FutureExt, Shared};
+use moka::sync::Cache;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tokio::sync::Semaphore;
+use tokio::time::Instant;
+use tracing::warn;
+
+// 最多缓存的文件夹数
+const CACHE_SIZE: u64 = 100_000;
+// 同时在后台统计的文件夹数
+const CONCURRENCY: usize = 4;
+// 最多排队等待统计的文件夹数，超过时新的文件夹本次不统计，之后访问时再排队
+const MAX_PENDING: usize = 256;
+
+#[derive(Clone, Copy)]
+pub struct DirSize {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+type Pending = Shared<BoxFuture<'static, Option<DirSize>>>;
+
+#[derive(Clone)]
+pub struct DirSizes {
+    // 文件夹 -> (统计结果, 统计时文件夹的修改时间)
+    // 直接子条目变化时修改时间随之改变; 更深层的变化由索引通知或缓存过期处理
+    cache: Cache<PathBuf, (DirSize, Option<SystemTime>)>,
+    // 正在统计的文件夹，同一文件夹的并发请求共用一次统计
+    pending: Arc<Mutex<HashMap<PathBuf, Pending>>>,
+    permits: Arc<Semaphore>,
+    budget: Duration,
+}
+
+impl DirSizes {
+    pub fn new(budget: Duration, ttl: Duration) -> Self {
+        Self {
+            cache: Cache::builder().max_capacity(CACHE_SIZE).time_to_live(ttl).build(),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            permits: Arc::new(Semaphore::new(CONCURRENCY)),
+            budget,
+        }
+    }
+
+    // 路径发生变化，它本身和所有上级文件夹的统计结果都已过期
+    pub fn invalidate(&self, path: &Path) {
+        for dir in path.ancestors() {
+            self.cache.invalidate(dir);
+        }
+    }
+
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+
+    async fn get(&self, state: &AppState, dir: PathBuf) -> Option<DirSize> {
+        let modified = fs::metadata(&dir).await.ok()?.modified().ok();
+        if let Some((size, cached)) = self.cache.get(&dir) {
+            if cached == modified {
+                return Some(size);
+            }
+        }
+
+        let task = {
+            let mut pending = self.pending.lock().unwrap();
+            if !pending.contains_key(&dir) && pending.len() >= MAX_PENDING {
+                return None;
+            }
+            pending
+                .entry(dir.clone())
+                .or_insert_with(|| {
+                    let (sizes, state, dir) = (self.clone(), state.clone(), dir.clone());
+                    // 在独立任务中统计，请求超过预算返回后也会继续
+                    tokio::spawn(async move {
+                        let size = match sizes.permits.acquire().await {
+                            Ok(_permit) => measure(&state, &dir).await,
+                            Err(_) => None,
+                        };
+                        if let Some(size) = size {
+                            sizes.cache.insert(dir.clone(), (size, modified));
+                        }
+                        sizes.pending.lock().unwrap().remove(&dir);
+                        size
+                    })
+                    .map(|result| result.ok().flatten())
+                    .boxed()
+                    .shared()
+                })
+                .clone()
+        };
+        task.await
+    }
+}
+
+// 与打包下载使用相同的遍历规则，隐藏和被排除的文件不计入
+async fn measure(state: &AppState, dir: &Path) -> Option<DirSize> {
+    let source = Source { path: dir.to_path_buf(), name: String::new() };
+    let mut walker = Walker::indexed(vec![source], state);
+    let mut size = DirSize { bytes: 0, files: 0 };
+    loop {
+        match walker.next().await {
+            Ok(Some(walk::Entry::File { size: bytes, .. })) => {
+                size.bytes = size.bytes.saturating_add(bytes);
+                size.files += 1;
+            }
+            Ok(Some(walk::Entry::Dir { .. })) => {}
+            Ok(None) => return Some(size),
+            Err(e) => {
+                warn!("Failed to measure {:?}: {:#}", dir, e);
+                return None;
+            }
+        }
+    }
+}
+
+// 为列表中的文件夹填上递归大小和文件数，预算内没统计完的保持未知
+// 调用方只传入当前页的条目，按大小排序时才需要传入整个目录
+pub async fn fill(state: &AppState, entries: &mut [Entry]) {
+    let Some(sizes) = &state.dir_sizes else {
+        return;
+    };
+    let deadline = Instant::now() + sizes.budget;
+    let results = future::join_all(entries.iter().map(|entry| async move {
+        if entry.kind != EntryKind::Dir {
+            return None;
+        }
+        let dir = state.resolve_path(Path::new(&entry.path))?;
+        tokio::time::timeout_at(deadline, sizes.get(state, dir)).await.ok().flatten()
+    }))
+    .await;
+
+    for (entry, size) in entries.iter_mut().zip(results) {
+        if let Some(size) = size {
+            entry.size = size.bytes;
+            entry.files = Some(size.files);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Content-Disposition (RFC 6266)，filename* 使用 RFC 5987 编码以支持中文文件名
+use axum::http::HeaderValue;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::collections::HashMap;
+
+// RFC 5987 attr-char 之外的字符都需要百分号编码
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!').remove(b'#').remove(b'$').remove(b'&').remove(b'+').remove(b'-')
+    .remove(b'.').remove(b'^').remove(b'_').remove(b'`').remove(b'|').remove(b'~');
+
+#[derive(Clone, Copy)]
+pub enum Disposition {
+    // 浏览器能显示的类型直接打开
+    Inline,
+    // 始终作为下载保存
+    Attachment,
+}
+
+impl Disposition {
+    // ?download=1 强制下载，?inline=1 强制在浏览器中打开，默认 inline 由浏览器按类型决定
+    pub fn from_query(query: &HashMap<String, String>) -> Self {
+        let enabled = |key: &str| query.get(key).is_some_and(|v| v != "0" && v != "false");
+        if enabled("download") && !enabled("inline") {
+            Disposition::Attachment
+        } else {
+            Disposition::Inline
+        }
+    }
+
+    pub fn header_value(self, file_name: &str) -> HeaderValue {
+        let kind = match self {
+            Disposition::Inline => "inline",
+            Disposition::Attachment => "attachment",
+        };
+        // 不支持 filename* 的旧客户端使用 ASCII 回退文件名
+        let fallback: String = file_name
+            .chars()
+            .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' })
+            .collect();
+        let value = format!(
+            "{}; filename=\"{}\"; filename*=UTF-8''{}",
+            kind,
+            fallback,
+            utf8_percent_encode(file_name, ATTR_CHAR)
+        );
+        HeaderValue::from_str(&value).unwrap_or(HeaderValue::from_static(kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_file_name() {
+        assert_eq!(
+            Disposition::Attachment.header_value("report 1.pdf"),
+            "attachment; filename=\"report 1.pdf\"; filename*=UTF-8''report%201.pdf"
+        );
+    }
+
+    #[test]
+    fn chinese_file_name() {
+        assert_eq!(
+            Disposition::Inline.header_value("报告.pdf"),
+            "inline; filename=\"__.pdf\"; filename*=UTF-8''%E6%8A%A5%E5%91%8A.pdf"
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_are_replaced() {
+        assert_eq!(
+            Disposition::Attachment.header_value("a\"b\\c.txt"),
+            "attachment; filename=\"a_b_c.txt\"; filename*=UTF-8''a%22b%5Cc.txt"
+        );
+    }
+
+    #[test]
+    fn from_query() {
+        let query = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        assert!(matches!(Disposition::from_query(&query(&[])), Disposition::Inline));
+        assert!(matches!(Disposition::from_query(&query(&[("download", "1")])), Disposition::Attachment));
+        assert!(matches!(Disposition::from_query(&query(&[("download", "0")])), Disposition::Inline));
+        assert!(matches!(
+            Disposition::from_query(&query(&[("download", "1"), ("inline", "1")])),
+            Disposition::Inline
+        ));
+    }
+}
@@ -0,0 +1,269 @@
+// 文件过滤: 隐藏点文件、--exclude / --include 规则，以及各目录下的 .shareignore / .gitignore
+// 列表、下载和打包使用同一套规则，被过滤的文件无法通过猜测 URL 访问
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use moka::sync::Cache;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// 分享者放在目录中的忽略文件，语法与 .gitignore 相同，始终生效
+const SHARE_IGNORE: &str = ".shareignore";
+const GIT_IGNORE: &str = ".gitignore";
+// 根目录下保存服务自身状态 (如未完成的上传) 的目录，任何规则下都不可见
+pub const STATE_DIR: &str = ".share_these";
+
+// 最多缓存的已解析忽略文件数
+const IGNORE_CACHE_SIZE: u64 = 10_000;
+
+pub struct FilterOptions {
+    pub exclude: Vec<String>,
+    pub include: Vec<String>,
+    pub hidden: bool,
+    pub gitignore: bool,
+}
+
+#[derive(Clone)]
+pub struct Filter {
+    // 规范化后的共享目录
+    roots: Arc<Vec<PathBuf>>,
+    // 显示以 . 开头的文件
+    hidden: bool,
+    exclude: Arc<Gitignore>,
+    // 为 None 时不限制文件
+    include: Option<Arc<Gitignore>>,
+    gitignore: bool,
+    // 按 (文件路径, 修改时间) 缓存解析过的忽略文件，文件修改后自动重新解析
+    ignore_files: Cache<(PathBuf, SystemTime), Arc<Gitignore>>,
+}
+
+impl Filter {
+    pub fn new(roots: Vec<PathBuf>, options: FilterOptions) -> Result<Self> {
+        let include = if options.include.is_empty() {
+            None
+        } else {
+            Some(Arc::new(build_patterns(&options.include).context("Invalid --include pattern")?))
+        };
+        Ok(Self {
+            roots: Arc::new(roots),
+            hidden: options.hidden,
+            exclude: Arc::new(build_patterns(&options.exclude).context("Invalid --exclude pattern")?),
+            include,
+            gitignore: options.gitignore,
+            ignore_files: Cache::new(IGNORE_CACHE_SIZE),
+        })
+    }
+
+    // 路径本身及其所有上级目录都没有被过滤
+    pub fn permits(&self, path: &Path, is_dir: bool) -> bool {
+        let (mut current, relative) = match self.roots.iter().find(|root| path.starts_with(root)) {
+            Some(root) => (DirFilter::new(self, root), path.strip_prefix(root).unwrap_or(Path::new(""))),
+            None => return false,
+        };
+
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            let last = components.peek().is_none();
+            if !current.permits(component.as_os_str(), is_dir || !last) {
+                return false;
+            }
+            if !last {
+                current.enter(component.as_os_str());
+            }
+        }
+        true
+    }
+
+    // 某个目录下条目的过滤器，目录本身应已通过 permits 检查
+    pub fn dir(&self, dir: &Path) -> DirFilter<'_> {
+        let root = self
+            .roots
+            .iter()
+            .find(|root| dir.starts_with(root))
+            .map(PathBuf::as_path)
+            .unwrap_or(dir);
+        let mut filter = DirFilter::new(self, root);
+        for component in dir.strip_prefix(root).unwrap_or(Path::new("")).components() {
+            filter.enter(component.as_os_str());
+        }
+        filter
+    }
+
+    // 目录下生效的忽略文件
+    fn ignore_files(&self, dir: &Path) -> Vec<Arc<Gitignore>> {
+        let names: &[&str] = if self.gitignore { &[GIT_IGNORE, SHARE_IGNORE] } else { &[SHARE_IGNORE] };
+        names
+            .iter()
+            .filter_map(|name| {
+                let path = dir.join(name);
+                let modified = std::fs::metadata(&path).ok()?.modified().ok()?;
+                let key = (path, modified);
+                Some(self.ignore_files.get_with(key.clone(), || {
+                    let (gitignore, _) = Gitignore::new(&key.0);
+                    Arc::new(gitignore)
+                }))
+            })
+            .collect()
+    }
+}
+
+// 一个目录下条目的过滤规则: 从共享目录到该目录的所有忽略文件，越深的优先
+pub struct DirFilter<'a> {
+    filter: &'a Filter,
+    dir: PathBuf,
+    // 相对于共享目录的路径，用于匹配 --exclude / --include
+    relative: PathBuf,
+    ignores: Vec<Arc<Gitignore>>,
+}
+
+impl<'a> DirFilter<'a> {
+    fn new(filter: &'a Filter, root: &Path) -> Self {
+        Self {
+            filter,
+            dir: root.to_path_buf(),
+            relative: PathBuf::new(),
+            ignores: filter.ignore_files(root),
+        }
+    }
+
+    fn enter(&mut self, name: &OsStr) {
+        self.dir.push(name);
+        self.relative.push(name);
+        self.ignores.extend(self.filter.ignore_files(&self.dir));
+    }
+
+    // 目录下名为 name 的条目是否可见
+    pub fn permits(&self, name: &OsStr, is_dir: bool) -> bool {
+        if self.relative.as_os_str().is_empty() && name == STATE_DIR {
+            return false;
+        }
+        if !self.filter.hidden && name.as_encoded_bytes().starts_with(b".") {
+            return false;
+        }
+
+        let relative = self.relative.join(name);
+        if self.filter.exclude.matched(&relative, is_dir).is_ignore() {
+            return false;
+        }
+
+        let path = self.dir.join(name);
+        for ignore in self.ignores.iter().rev() {
+            match ignore.matched(&path, is_dir) {
+                Match::Ignore(_) => return false,
+                Match::Whitelist(_) => break,
+                Match::None => {}
+            }
+        }
+
+        // --include 只限制文件，目录始终可见以便浏览
+        match &self.filter.include {
+            Some(include) if !is_dir => include.matched(&relative, false).is_ignore(),
+            _ => true,
+        }
+    }
+}
+
+// 命令行中的规则按 .gitignore 语法解析，相对于每个共享目录匹配
+fn build_patterns(patterns: &[String]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .with_context(|| format!("Invalid pattern: {}", pattern))?;
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 每个测试使用独立的临时目录，files 中以 / 结尾的为目录
+    fn setup(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("share_these_filter_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        for (path, content) in files {
+            let path = root.join(path);
+            if path.to_string_lossy().ends_with('/') {
+                std::fs::create_dir_all(&path).unwrap();
+            } else {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, content).unwrap();
+            }
+        }
+        root
+    }
+
+    fn filter(root: &Path, exclude: &[&str], include: &[&str], hidden: bool) -> Filter {
+        let options = FilterOptions {
+            exclude: exclude.iter().map(|p| p.to_string()).collect(),
+            include: include.iter().map(|p| p.to_string()).collect(),
+            hidden,
+            gitignore: false,
+        };
+        Filter::new(vec![root.to_path_buf()], options).unwrap()
+    }
+
+    #[test]
+    fn hidden_files() {
+        let root = setup("hidden", &[(".env", ""), (".git/", ""), ("a.txt", "")]);
+        let default = filter(&root, &[], &[], false);
+        assert!(!default.permits(&root.join(".env"), false));
+        assert!(!default.permits(&root.join(".git/config"), false));
+        assert!(default.permits(&root.join("a.txt"), false));
+
+        let hidden = filter(&root, &[], &[], true);
+        assert!(hidden.permits(&root.join(".env"), false));
+        assert!(hidden.permits(&root.join(".git/config"), false));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn exclude_and_include() {
+        let root = setup("globs", &[("sub/", "")]);
+        let exclude = filter(&root, &["*.log", "/build"], &[], false);
+        assert!(!exclude.permits(&root.join("a.log"), false));
+        assert!(!exclude.permits(&root.join("sub/b.log"), false));
+        assert!(!exclude.permits(&root.join("build/out.txt"), false));
+        assert!(exclude.permits(&root.join("sub/build"), true));
+        assert!(exclude.permits(&root.join("a.txt"), false));
+
+        // --include 只限制文件，目录仍可浏览
+        let include = filter(&root, &[], &["*.pdf"], false);
+        assert!(include.permits(&root.join("sub/a.pdf"), false));
+        assert!(!include.permits(&root.join("sub/a.txt"), false));
+        assert!(include.permits(&root.join("sub"), true));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn nested_shareignore_whitelist() {
+        let root = setup(
+            "nested",
+            &[(SHARE_IGNORE, "secret.txt\n"), ("sub/.shareignore", "!secret.txt\n"), ("other/", "")],
+        );
+        let filter = filter(&root, &[], &[], false);
+        assert!(!filter.permits(&root.join("secret.txt"), false));
+        assert!(!filter.permits(&root.join("other/secret.txt"), false));
+        assert!(filter.permits(&root.join("sub/secret.txt"), false));
+
+        let dir = filter.dir(&root.join("sub"));
+        assert!(dir.permits(OsStr::new("secret.txt"), false));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn state_dir_always_hidden() {
+        let root = setup("state", &[(SHARE_IGNORE, "!.share_these\n"), (".share_these/uploads/", "")]);
+        let filter = filter(&root, &[], &[], true);
+        assert!(!filter.permits(&root.join(STATE_DIR), true));
+        assert!(!filter.permits(&root.join(".share_these/uploads/x.part"), false));
+        assert!(!filter.dir(&root).permits(OsStr::new(STATE_DIR), true));
+        // 只有根目录下的状态目录被隐藏
+        assert!(filter.permits(&root.join("sub/.share_these"), true));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,363 @@
+// 共享目录的内存索引 (--index): 启动时在后台建立，之后通过文件系统通知 (Linux 上为 inotify) 保持更新
+// 索引就绪后目录列表和搜索直接查询索引，建立期间或监听出错时回退到读取磁盘
+use crate::dirsize::DirSizes;
+use crate::AppState;
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Json,
+};
+use notify::event::{AccessKind, AccessMode};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+// 收到通知后稍等片刻再更新，合并同一批修改产生的多个事件
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// 目录中的一个条目，不跟随符号链接: 符号链接记录链接本身，由读取方按符号链接策略解析
+#[derive(Clone)]
+pub struct Node {
+    pub name: OsString,
+    pub is_dir: bool,
+    pub symlink: bool,
+    // 目录为 0
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Node {
+    pub fn new(name: OsString, metadata: &std::fs::Metadata) -> Self {
+        Self {
+            name,
+            is_dir: metadata.is_dir(),
+            symlink: metadata.file_type().is_symlink(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Phase {
+    // 未启用 --index
+    #[default]
+    Disabled,
+    Building,
+    Ready,
+    // 监听出错，已回退到读取磁盘
+    Failed,
+}
+
+struct Status {
+    phase: Phase,
+    error: Option<String>,
+    built_at: Option<SystemTime>,
+    build_time: Option<Duration>,
+    updated_at: Option<SystemTime>,
+    // 处理过的通知批次数
+    updates: u64,
+    // 通知队列溢出等原因导致的完整重建次数
+    rebuilds: u64,
+}
+
+#[derive(Clone)]
+pub struct Index {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    roots: Vec<PathBuf>,
+    // 目录 -> 直接子条目; 按路径排序，一个目录的整个子树是一段连续区间
+    dirs: RwLock<BTreeMap<PathBuf, Arc<Vec<Node>>>>,
+    status: Mutex<Status>,
+    // 出错时丢弃以停止监听
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    // 文件变化时使相关文件夹的大小统计过期
+    sizes: Option<DirSizes>,
+}
+
+impl Index {
+    // 开始监听共享目录，并在后台建立索引
+    pub fn start(roots: Vec<PathBuf>, sizes: Option<DirSizes>) -> Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = RecommendedWatcher::new(
+            move |event| {
+                let _ = tx.send(event);
+            },
+            Config::default().with_follow_symlinks(false),
+        )
+        .context("Failed to create file watcher")?;
+        for root in &roots {
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch {:?} (check fs.inotify.max_user_watches)", root))?;
+        }
+
+        let index = Self {
+            inner: Arc::new(Inner {
+                roots,
+                dirs: RwLock::new(BTreeMap::new()),
+                status: Mutex::new(Status {
+                    phase: Phase::Building,
+                    error: None,
+                    built_at: None,
+                    build_time: None,
+                    updated_at: None,
+                    updates: 0,
+                    rebuilds: 0,
+                }),
+                watcher: Mutex::new(Some(watcher)),
+                sizes,
+            }),
+        };
+        tokio::spawn(index.clone().run(rx));
+        Ok(index)
+    }
+
+    // 目录的直接子条目 (未经过滤)，索引未就绪或目录不在索引中时返回 None
+    pub fn children(&self, dir: &Path) -> Option<Arc<Vec<Node>>> {
+        if self.inner.status.lock().unwrap().phase != Phase::Ready {
+            return None;
+        }
+        self.inner.dirs.read().unwrap().get(dir).cloned()
+    }
+
+    // 先建立索引，期间收到的通知留在队列中，建好后再依次处理
+    async fn run(self, mut events: mpsc::UnboundedReceiver<notify::Result<Event>>) {
+        self.rebuild().await;
+
+        while let Some(event) = events.recv().await {
+            tokio::time::sleep(DEBOUNCE).await;
+            let mut batch = vec![event];
+            while let Ok(event) = events.try_recv() {
+                batch.push(event);
+            }
+
+            // 需要重新读取的路径: 事件路径本身 (是目录时) 和它的上级目录
+            let mut paths = BTreeSet::new();
+            let mut overflowed = false;
+            for event in batch {
+                match event {
+                    Ok(event) if event.need_rescan() => overflowed = true,
+                    // 读取文件和目录 (包括建立索引本身) 产生的访问事件不影响索引，写入后关闭的除外
+                    Ok(event) if matches!(event.kind, EventKind::Access(kind) if kind != AccessKind::Close(AccessMode::Write)) => {}
+                    Ok(event) => {
+                        for path in event.paths {
+                            if let Some(parent) = path.parent() {
+                                paths.insert(parent.to_path_buf());
+                            }
+                            paths.insert(path);
+                        }
+                    }
+                    Err(e) => {
+                        self.fail(format!("File watcher error: {}", e));
+                        return;
+                    }
+                }
+            }
+
+            if overflowed {
+                warn!("File watcher queue overflowed, rebuilding index");
+                self.inner.status.lock().unwrap().rebuilds += 1;
+                self.rebuild().await;
+                continue;
+            }
+
+            if paths.is_empty() {
+                continue;
+            }
+            let index = self.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || index.update(paths)).await {
+                self.fail(format!("Failed to update index: {}", e));
+                return;
+            }
+        }
+    }
+
+    // 完整扫描所有共享目录，期间查询回退到读取磁盘
+    async fn rebuild(&self) {
+        self.inner.status.lock().unwrap().phase = Phase::Building;
+        let started = Instant::now();
+        let roots = self.inner.roots.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let mut dirs = BTreeMap::new();
+            for root in &roots {
+                scan_tree(root, &mut dirs);
+            }
+            dirs
+        })
+        .await;
+
+        match result {
+            Ok(dirs) => {
+                let count = dirs.len();
+                *self.inner.dirs.write().unwrap() = dirs;
+                if let Some(sizes) = &self.inner.sizes {
+                    sizes.invalidate_all();
+                }
+                let mut status = self.inner.status.lock().unwrap();
+                status.phase = Phase::Ready;
+                status.built_at = Some(SystemTime::now());
+                status.build_time = Some(started.elapsed());
+                info!("Index built: {} directories in {:?}", count, started.elapsed());
+            }
+            Err(e) => self.fail(format!("Failed to build index: {}", e)),
+        }
+    }
+
+    // 停止监听，之后所有查询都回退到读取磁盘
+    fn fail(&self, message: String) {
+        error!("{}, falling back to reading from disk", message);
+        self.inner.watcher.lock().unwrap().take();
+        self.inner.dirs.write().unwrap().clear();
+        let mut status = self.inner.status.lock().unwrap();
+        status.phase = Phase::Failed;
+        status.error = Some(message);
+    }
+
+    fn update(&self, paths: BTreeSet<PathBuf>) {
+        for path in paths {
+            if !self.inner.roots.iter().any(|root| path.starts_with(root)) {
+                continue;
+            }
+            match std::fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.is_dir() => self.rescan(&path),
+                // 文件的变化由上级目录的重新读取处理
+                Ok(_) => {}
+                Err(_) => self.remove_tree(&path),
+            }
+            if let Some(sizes) = &self.inner.sizes {
+                sizes.invalidate(&path);
+            }
+        }
+        let mut status = self.inner.status.lock().unwrap();
+        status.updated_at = Some(SystemTime::now());
+        status.updates += 1;
+    }
+
+    // 重新读取一个目录: 移除消失的子目录，为新出现的子目录建立索引
+    fn rescan(&self, dir: &Path) {
+        let Ok(children) = read_children(dir) else {
+            self.remove_tree(dir);
+            return;
+        };
+        let old = self.inner.dirs.read().unwrap().get(dir).cloned();
+        for node in old.iter().flat_map(|old| old.iter()).filter(|node| node.is_dir) {
+            if !children.iter().any(|child| child.is_dir && child.name == node.name) {
+                self.remove_tree(&dir.join(&node.name));
+            }
+        }
+
+        let mut added = BTreeMap::new();
+        {
+            let dirs = self.inner.dirs.read().unwrap();
+            for child in children.iter().filter(|child| child.is_dir) {
+                let path = dir.join(&child.name);
+                if !dirs.contains_key(&path) {
+                    scan_tree(&path, &mut added);
+                }
+            }
+        }
+        let mut dirs = self.inner.dirs.write().unwrap();
+        dirs.insert(dir.to_path_buf(), Arc::new(children));
+        dirs.extend(added);
+    }
+
+    fn remove_tree(&self, dir: &Path) {
+        let mut dirs = self.inner.dirs.write().unwrap();
+        let subtree: Vec<PathBuf> = dirs
+            .range(dir.to_path_buf()..)
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(dir))
+            .cloned()
+            .collect();
+        for path in subtree {
+            dirs.remove(&path);
+        }
+    }
+
+    fn status(&self) -> IndexStatus {
+        let status = self.inner.status.lock().unwrap();
+        let dirs = self.inner.dirs.read().unwrap();
+        let files = dirs.values().flat_map(|children| children.iter()).filter(|node| !node.is_dir);
+        let (files, bytes) = files.fold((0, 0), |(count, bytes), node| (count + 1, bytes + node.size));
+        IndexStatus {
+            state: status.phase,
+            error: status.error.clone(),
+            directories: dirs.len(),
+            files,
+            bytes,
+            built_at: status.built_at.and_then(crate::unix_secs),
+            build_ms: status.build_time.map(|d| d.as_millis()),
+            updated_at: status.updated_at.and_then(crate::unix_secs),
+            updates: status.updates,
+            rebuilds: status.rebuilds,
+        }
+    }
+}
+
+// 递归读取目录树，不进入符号链接指向的目录; 无法读取的目录跳过
+fn scan_tree(root: &Path, dirs: &mut BTreeMap<PathBuf, Arc<Vec<Node>>>) {
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let children = match read_children(&dir) {
+            Ok(children) => children,
+            Err(e) => {
+                warn!("Skipping {:?} in index: {}", dir, e);
+                continue;
+            }
+        };
+        stack.extend(children.iter().filter(|child| child.is_dir).map(|child| dir.join(&child.name)));
+        dirs.insert(dir, Arc::new(children));
+    }
+}
+
+fn read_children(dir: &Path) -> std::io::Result<Vec<Node>> {
+    Ok(std::fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            Some(Node::new(entry.file_name(), &metadata))
+        })
+        .collect())
+}
+
+#[derive(Default, Serialize)]
+struct IndexStatus {
+    state: Phase,
+    error: Option<String>,
+    directories: usize,
+    files: u64,
+    // 所有文件的总大小
+    bytes: u64,
+    // Unix 时间戳 (秒)
+    built_at: Option<u64>,
+    build_ms: Option<u128>,
+    updated_at: Option<u64>,
+    updates: u64,
+    rebuilds: u64,
+}
+
+#[derive(Serialize)]
+struct ServerStatus {
+    version: &'static str,
+    index: IndexStatus,
+}
+
+// 服务器状态: GET /status 返回 JSON
+pub async fn status(State(state): State<AppState>) -> Response {
+    let index = match &state.index {
+        Some(index) => index.status(),
+        None => IndexStatus::default(),
+    };
+    Json(ServerStatus { version: crate::PKG_VERSION, index }).into_response()
+}
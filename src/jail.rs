@@ -0,0 +1,137 @@
+// 路径解析的安全检查: 拒绝目录穿越，并按符号链接策略确认路径留在共享目录内
+use percent_encoding::percent_decode_str;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+// 符号链接策略
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    // 只跟随指向共享目录内部的符号链接 (默认)
+    Jailed,
+    // 跟随所有符号链接 (--follow-symlinks)
+    Follow,
+    // 拒绝经过任何符号链接的路径 (--no-symlinks)
+    Deny,
+}
+
+// 把 URL 中 (已解码一次) 的路径拆成相对路径
+// 任何一段是 . 或 ..、再解码一次后是 . 或 .. 或包含 /、含有反斜杠或 NUL 时返回 None
+// 文件名中间出现 .. (如 notes..txt) 是合法的
+pub fn relative_path(path: &str) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for segment in path.split('/').filter(|s| !s.is_empty()) {
+        if segment.contains(['\\', '\0']) {
+            return None;
+        }
+        // 只允许普通文件名，排除 . / .. 和 Windows 盘符等
+        if !Path::new(segment).components().all(|c| matches!(c, Component::Normal(_))) {
+            return None;
+        }
+        // 二次编码的穿越 (%252e%252e -> %2e%2e -> ..)
+        let decoded = percent_decode_str(segment).decode_utf8_lossy();
+        if matches!(decoded.as_ref(), "." | "..") || decoded.contains(['/', '\\', '\0']) {
+            return None;
+        }
+        relative.push(segment);
+    }
+    Some(relative)
+}
+
+#[derive(Clone)]
+pub struct Jail {
+    // 规范化后的共享目录
+    roots: Arc<Vec<PathBuf>>,
+    policy: SymlinkPolicy,
+}
+
+impl Jail {
+    pub fn new(roots: Vec<PathBuf>, policy: SymlinkPolicy) -> Self {
+        Self { roots: Arc::new(roots), policy }
+    }
+
+    // 路径是否符合符号链接策略，路径本身可以尚不存在 (如上传的目标)
+    pub fn permits(&self, path: &Path) -> bool {
+        let Some(root) = self.roots.iter().find(|root| path.starts_with(root)) else {
+            return false;
+        };
+        match self.policy {
+            SymlinkPolicy::Follow => true,
+            // 规范化最深的已存在部分，结果必须仍在共享目录内
+            SymlinkPolicy::Jailed => path
+                .ancestors()
+                .find_map(|p| std::fs::canonicalize(p).ok())
+                .is_some_and(|real| real.starts_with(root)),
+            // 逐级检查，路径上任何一级是符号链接都拒绝
+            SymlinkPolicy::Deny => {
+                let mut current = root.clone();
+                for component in path.strip_prefix(root).unwrap_or(Path::new("")).components() {
+                    current.push(component);
+                    match std::fs::symlink_metadata(&current) {
+                        Ok(metadata) if metadata.file_type().is_symlink() => return false,
+                        Ok(_) => {}
+                        Err(_) => break,
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_path_accepts_normal_segments() {
+        assert_eq!(relative_path(""), Some(PathBuf::new()));
+        assert_eq!(relative_path("/a//b/"), Some(PathBuf::from("a/b")));
+        assert_eq!(relative_path("notes..txt"), Some(PathBuf::from("notes..txt")));
+        assert_eq!(relative_path("..hidden/中文.txt"), Some(PathBuf::from("..hidden/中文.txt")));
+    }
+
+    #[test]
+    fn relative_path_rejects_traversal() {
+        for path in ["..", "a/../b", ".", "a/./b", "a\\..\\b", "a\0b", "%2e%2e", "a/%2E%2e/b", "%2e", "a%2fb", "a%5cb"] {
+            assert_eq!(relative_path(path), None, "{:?}", path);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_policies() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("share_these_jail_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (root, outside) = (base.join("root"), base.join("outside"));
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("dir/file.txt"), "x").unwrap();
+        std::fs::write(outside.join("secret.txt"), "x").unwrap();
+        symlink(root.join("dir/file.txt"), root.join("inside")).unwrap();
+        symlink(outside.join("secret.txt"), root.join("escape")).unwrap();
+        symlink(&outside, root.join("escape_dir")).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let jailed = Jail::new(vec![root.clone()], SymlinkPolicy::Jailed);
+        assert!(jailed.permits(&root.join("dir/file.txt")));
+        assert!(jailed.permits(&root.join("dir/new.txt")));
+        assert!(jailed.permits(&root.join("inside")));
+        assert!(!jailed.permits(&root.join("escape")));
+        assert!(!jailed.permits(&root.join("escape_dir/secret.txt")));
+        assert!(!jailed.permits(&root.join("escape_dir/new.txt")));
+        assert!(!jailed.permits(&outside.join("secret.txt")));
+
+        let deny = Jail::new(vec![root.clone()], SymlinkPolicy::Deny);
+        assert!(deny.permits(&root.join("dir/file.txt")));
+        assert!(!deny.permits(&root.join("inside")));
+        assert!(!deny.permits(&root.join("escape_dir/secret.txt")));
+
+        let follow = Jail::new(vec![root.clone()], SymlinkPolicy::Follow);
+        assert!(follow.permits(&root.join("escape")));
+        assert!(!follow.permits(&outside.join("secret.txt")));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+// 目录列表的条目，以及供脚本使用的 JSON 格式
+use axum::{
+    http::{
+        header::{ACCEPT, COOKIE, SET_COOKIE},
+        HeaderMap, HeaderValue,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Dir,
+    File,
+}
+
+// 目录中的一个文件或文件夹
+#[derive(Serialize)]
+pub struct Entry {
+    pub name: String,
+    // 相对路径，与 /files/ 后的路径相同
+    pub path: String,
+    #[serde(rename = "type")]
+    pub kind: EntryKind,
+    // 文件夹为递归统计的总大小，未统计完成时为 0
+    pub size: u64,
+    // 修改时间 (Unix 时间戳，秒)
+    pub mtime: Option<u64>,
+    // 按扩展名判断的 MIME 类型，文件夹和未知类型为 null
+    pub mime: Option<String>,
+    // 文件夹内 (递归) 的文件数，文件以及未在时间预算内统计完成的文件夹为 null
+    pub files: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Mtime,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 3] = [SortKey::Name, SortKey::Size, SortKey::Mtime];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Mtime => "mtime",
+        }
+    }
+}
+
+// 列表排序方式: ?sort=name|size|mtime&order=asc|desc，默认按名称升序
+#[derive(Clone, Copy)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    // 无法识别的参数按默认值处理
+    pub fn from_query(query: &HashMap<String, String>) -> Self {
+        let key = match query.get("sort").map(String::as_str) {
+            Some("size") => SortKey::Size,
+            Some("mtime") => SortKey::Mtime,
+            _ => SortKey::Name,
+        };
+        let descending = query.get("order").is_some_and(|order| order == "desc");
+        Self { key, descending }
+    }
+
+    // 文件夹始终在文件之前，大小或修改时间相同时按名称排序
+    pub fn apply(&self, entries: &mut [Entry]) {
+        entries.sort_by(|a, b| {
+            let folders_first = (a.kind == EntryKind::File).cmp(&(b.kind == EntryKind::File));
+            let ordering = match self.key {
+                SortKey::Name => Ordering::Equal,
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Mtime => a.mtime.cmp(&b.mtime),
+            }
+            .then_with(|| natural_cmp(&a.name, &b.name));
+            folders_first.then(if self.descending { ordering.reverse() } else { ordering })
+        });
+    }
+}
+
+// 保存视图选择的 cookie 名
+const VIEW_COOKIE: &str = "share_these_view";
+
+// 页面布局: 卡片网格或详细列表
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Grid,
+    Table,
+}
+
+impl View {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            View::Grid => "grid",
+            View::Table => "table",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "grid" => Some(View::Grid),
+            "table" => Some(View::Table),
+            _ => None,
+        }
+    }
+}
+
+// 每页默认显示的条目数和允许的上限
+const DEFAULT_PAGE_SIZE: usize = 500;
+const MAX_PAGE_SIZE: usize = 5000;
+
+// 分页参数: ?page= (从 1 开始)、?limit=，以及 ?cursor= (上一页最后一项的路径)
+// 有 cursor 时从该条目之后开始，目录内容在翻页期间变化也不会重复或遗漏
+#[derive(Clone)]
+pub struct Pagination {
+    pub page: usize,
+    pub limit: usize,
+    pub cursor: Option<String>,
+}
+
+impl Pagination {
+    fn from_query(query: &HashMap<String, String>) -> Self {
+        let page = query.get("page").and_then(|p| p.parse().ok()).filter(|&p| p > 0).unwrap_or(1);
+        let limit = query
+            .get("limit")
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let cursor = query.get("cursor").filter(|c| !c.is_empty()).cloned();
+        Self { page, limit, cursor }
+    }
+
+    // 从已排序的条目中取出当前页
+    pub fn apply(&self, mut entries: Vec<Entry>, truncated: bool) -> Page {
+        let total = entries.len();
+        let start = match self.cursor.as_deref().and_then(|c| entries.iter().position(|e| e.path == c)) {
+            Some(index) => index + 1,
+            None => (self.page - 1).saturating_mul(self.limit),
+        }
+        .min(total);
+        let end = start.saturating_add(self.limit).min(total);
+        let next_cursor = (start < end && end < total).then(|| entries[end - 1].path.clone());
+        Page {
+            entries: entries.drain(start..end).collect(),
+            page: start / self.limit + 1,
+            pages: total.div_ceil(self.limit).max(1),
+            limit: self.limit,
+            total,
+            next_cursor,
+            truncated,
+        }
+    }
+}
+
+// 当前页的条目和分页信息
+#[derive(Serialize)]
+pub struct Page {
+    pub entries: Vec<Entry>,
+    pub page: usize,
+    pub pages: usize,
+    pub limit: usize,
+    // 当前列出的条目总数 (达到上限时不是目录中的实际数量)
+    pub total: usize,
+    // 下一页的 cursor，最后一页为 null
+    pub next_cursor: Option<String>,
+    // 目录条目超过 --max-entries，只列出了其中一部分
+    pub truncated: bool,
+}
+
+// 列表页面的显示选项
+#[derive(Clone)]
+pub struct ListingOptions {
+    pub sort: Sort,
+    pub view: View,
+    pub pagination: Pagination,
+    // 视图来自 ?view= 时需要写入 cookie
+    view_from_query: bool,
+}
+
+impl ListingOptions {
+    // ?view= 优先，其次是上次保存的 cookie，默认卡片视图
+    pub fn from_request(query: &HashMap<String, String>, headers: &HeaderMap) -> Self {
+        let from_query = query.get("view").and_then(|v| View::parse(v));
+        let from_cookie = || {
+            headers
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(name, _)| *name == VIEW_COOKIE)
+                .and_then(|(_, value)| View::parse(value))
+        };
+        Self {
+            sort: Sort::from_query(query),
+            view: from_query.or_else(from_cookie).unwrap_or(View::Grid),
+            pagination: Pagination::from_query(query),
+            view_from_query: from_query.is_some(),
+        }
+    }
+
+    // 记住通过 ?view= 切换的视图，之后访问其他目录时沿用
+    pub fn remember(&self, response: &mut Response) {
+        if !self.view_from_query {
+            return;
+        }
+        let cookie = format!("{}={}; Path=/; Max-Age=31536000; SameSite=Lax", VIEW_COOKIE, self.view.as_str());
+        if let Ok(value) = HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(SET_COOKIE, value);
+        }
+    }
+}
+
+// 自然排序: 数字部分按数值比较 (file9 < file10)，其余部分忽略大小写，完全相同时按原始字节比较
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let m = take_digits(&mut x);
+                let n = take_digits(&mut y);
+                // 去掉前导零后先比较位数，再逐位比较
+                let (m, n) = (m.trim_start_matches('0'), n.trim_start_matches('0'));
+                let ordering = m.len().cmp(&n.len()).then_with(|| m.cmp(n));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(c), Some(d)) => {
+                let ordering = c.to_lowercase().cmp(d.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                x.next();
+                y.next();
+            }
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[derive(Serialize)]
+struct Listing<'a> {
+    path: &'a str,
+    #[serde(flatten)]
+    page: &'a Page,
+}
+
+// Accept: application/json 或 ?format=json 时返回 JSON
+pub fn wants_json(headers: &HeaderMap, query: &HashMap<String, String>) -> bool {
+    query.get("format").is_some_and(|f| f == "json")
+        || headers
+            .get(ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|accept| accept.contains("application/json"))
+}
+
+pub fn render_json(current_path: &str, page: &Page) -> Response {
+    Json(Listing { path: current_path, page }).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    #[test]
+    fn natural_cmp_numbers() {
+        assert_eq!(sorted(&["file10", "file9", "file1"]), ["file1", "file9", "file10"]);
+        assert_eq!(sorted(&["v1.10", "v1.9", "v1.2"]), ["v1.2", "v1.9", "v1.10"]);
+        // 数值相同时按原始字节比较
+        assert_eq!(natural_cmp("a007", "a7"), Ordering::Less);
+        assert_eq!(natural_cmp("a18446744073709551616", "a9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn natural_cmp_case_and_prefix() {
+        assert_eq!(sorted(&["b", "A", "a", "B"]), ["A", "a", "B", "b"]);
+        assert_eq!(natural_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(natural_cmp("报告2", "报告10"), Ordering::Less);
+    }
+}
@@ -1,15 +1,19 @@
 use axum::{
-    extract::{ConnectInfo, Path, State},
-    http::{header::{CONTENT_TYPE, CONTENT_LENGTH, RANGE, ACCEPT_RANGES, CONTENT_RANGE}, StatusCode, HeaderMap},
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header::{CONTENT_TYPE, CONTENT_LENGTH, RANGE, ACCEPT_RANGES, CONTENT_RANGE, CONTENT_ENCODING, CONTENT_DISPOSITION, CONTENT_SECURITY_POLICY, VARY, X_CONTENT_TYPE_OPTIONS}, StatusCode, HeaderMap, HeaderValue},
     response::{Html, IntoResponse, Response},
-    routing::get,
+    middleware,
+    routing::{get, post},
     Router,
-    body::Body,
+    body::{Body, Bytes},
+    extract::DefaultBodyLimit,
 };
-use std::net::SocketAddr;
-use std::{path::PathBuf, sync::Arc, io::SeekFrom};
+use std::net::{IpAddr, SocketAddr};
+use std::{path::PathBuf, sync::{Arc, Mutex}, io::SeekFrom};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use tokio::fs::{self, File};
-use tokio::io::{AsyncSeekExt, AsyncRead, AsyncReadExt};
+use tokio::io::{AsyncSeekExt, AsyncRead};
 use tower_http::trace::TraceLayer;
 use tracing::{error, info, Level};
 use tracing_subscriber::FmtSubscriber;
@@ -18,9 +22,37 @@ use tokio_util::io::ReaderStream;
 use std::cmp::min;
 use clap::Parser;
 use tower::limit::ConcurrencyLimitLayer;
-use moka::future::Cache;
+use futures_util::{future, stream, StreamExt, TryStreamExt};
 
+mod archive;
+mod auth;
+mod cache;
+mod compression;
+mod conditional;
+mod jail;
+mod listing;
+mod digest;
+mod dirsize;
+mod disposition;
+mod filter;
+mod index;
+mod mime;
+mod search;
 mod templates;
+mod tus;
+mod upload;
+mod walk;
+use auth::{AuthUser, UserStore};
+use cache::{CachedFile, FileCache};
+use conditional::{EtagMode, Etags, Validators};
+use digest::Digests;
+use dirsize::DirSizes;
+use disposition::Disposition;
+use filter::{DirFilter, Filter, FilterOptions};
+use index::{Index, Node};
+use jail::{Jail, SymlinkPolicy};
+use listing::{Entry, EntryKind, ListingOptions, Page, SortKey};
+use mime::MimeTypes;
 use templates::render_file_list;
 
 // 命令行参数定义
@@ -30,9 +62,15 @@ use templates::render_file_list;
     author = PKG_AUTHORS,
     version = PKG_VERSION,
     about = PKG_DESCRIPTION,
-    long_about = "分享当前目录(包括子目录)下的所有文件"
+    long_about = "分享指定目录(包括子目录)下的所有文件, 未指定目录时分享当前目录",
+    // -h 已用于 --host, 帮助信息只保留 --help
+    disable_help_flag = true
 )]
 struct Args {
+    /// 要分享的目录, 可指定多个 (默认为当前目录)
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
     /// 服务器绑定的端口
     #[arg(short, long, default_value_t = 3000)]
     port: u16,
@@ -40,6 +78,107 @@ struct Args {
     /// 服务器绑定的网卡地址
     #[arg(short, long, default_value = "0.0.0.0")]
     host: String,
+
+    /// 访问令牌, 不带值时启动时随机生成 (指定值时使用 --token=TOKEN)
+    // require_equals: 避免 --token 把后面的目录参数当作令牌
+    #[arg(long, value_name = "TOKEN", require_equals = true, num_args = 0..=1)]
+    token: Option<Option<String>>,
+
+    /// htpasswd 风格的账号文件 (user:hash, 支持 bcrypt/argon2), 启用 Basic 认证
+    #[arg(long, value_name = "FILE")]
+    auth_file: Option<PathBuf>,
+
+    /// 允许上传文件到共享目录
+    #[arg(long)]
+    allow_upload: bool,
+
+    /// 单次上传的大小限制 (支持 K/M/G 单位)
+    #[arg(long, value_name = "SIZE", default_value = "1G", value_parser = parse_size)]
+    upload_limit: u64,
+
+    /// 多选打包下载的总大小上限 (支持 K/M/G 单位)
+    #[arg(long, value_name = "SIZE", default_value = "4G", value_parser = parse_size)]
+    select_limit: u64,
+
+    /// ETag 生成方式: metadata (大小+修改时间+inode) 或 content (内容哈希)
+    #[arg(long, value_enum, value_name = "MODE", default_value = "metadata")]
+    etag: EtagMode,
+
+    /// 小文件缓存占用的内存上限 (支持 K/M/G 单位)
+    #[arg(long, value_name = "SIZE", default_value = "64M", value_parser = parse_size)]
+    cache_max_bytes: u64,
+
+    /// 可以被缓存的单个文件大小上限 (支持 K/M/G 单位)
+    #[arg(long, value_name = "SIZE", default_value = "1M", value_parser = parse_size)]
+    cache_max_file_size: u64,
+
+    /// 禁用小文件缓存
+    #[arg(long)]
+    no_cache: bool,
+
+    /// 小文件缓存的过期时间 (秒)，不指定时只在文件变化时失效
+    #[arg(long, value_name = "SECONDS")]
+    cache_ttl: Option<u64>,
+
+    /// 自定义扩展名对应的 MIME 类型, 可重复指定 (如 log=text/plain)
+    #[arg(long, value_name = "EXT=TYPE", value_parser = mime::parse_override)]
+    mime: Vec<(String, String)>,
+
+    /// 排除匹配的文件和目录 (.gitignore 语法, 可重复指定), 如 --exclude target/ --exclude '*.key'
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// 只分享匹配的文件 (.gitignore 语法, 可重复指定), 目录始终可见
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// 显示并允许下载以 . 开头的隐藏文件
+    #[arg(long)]
+    hidden: bool,
+
+    /// 遵循各目录下的 .gitignore (.shareignore 始终生效)
+    #[arg(long)]
+    gitignore: bool,
+
+    /// 跟随所有符号链接, 即使指向共享目录之外 (默认只跟随指向共享目录内部的符号链接)
+    #[arg(long, conflicts_with = "no_symlinks")]
+    follow_symlinks: bool,
+
+    /// 拒绝访问任何经过符号链接的路径
+    #[arg(long)]
+    no_symlinks: bool,
+
+    /// 禁用响应压缩 (包括预压缩的 .br / .gz 文件)
+    #[arg(long)]
+    no_compression: bool,
+
+    /// 单个目录最多列出的条目数, 超过时只显示前面部分并提示缩小范围
+    #[arg(long, value_name = "N", default_value_t = 100_000)]
+    max_entries: usize,
+
+    /// 每次搜索最多返回的结果数
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    search_limit: usize,
+
+    /// 搜索超时时间 (秒), 超时后返回已找到的结果
+    #[arg(long, value_name = "SECS", default_value_t = 10)]
+    search_timeout: u64,
+
+    /// 在内存中为共享目录建立索引, 通过文件系统通知保持更新, 加快大目录列表和搜索
+    #[arg(long)]
+    index: bool,
+
+    /// 每次列表等待统计文件夹大小的时间 (毫秒), 超时的文件夹在后台继续统计; 0 为不统计
+    #[arg(long, value_name = "MS", default_value_t = 300)]
+    dir_size_budget: u64,
+
+    /// 文件夹大小统计结果的缓存时间 (秒)
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    dir_size_ttl: u64,
+
+    /// 显示帮助信息
+    #[arg(long, action = clap::ArgAction::Help)]
+    help: Option<bool>,
 }
 
 // 作者信息结构体
@@ -58,16 +197,120 @@ const PKG_AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 const PKG_DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
 const PKG_REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
 
+// 共享的根目录，alias 作为 URL 前缀 (/files/<alias>/...)
+#[derive(Clone)]
+struct SharedRoot {
+    alias: String,
+    path: PathBuf,
+}
+
 // 应用状态，存储根目录路径和作者信息
 #[derive(Clone)]
 struct AppState {
-    root_dir: Arc<PathBuf>,
+    roots: Arc<Vec<SharedRoot>>,
     author: Author,
-    cache: Cache<String, Vec<u8>>,
+    cache: FileCache,
+    token: Option<Arc<str>>,
+    users: Option<Arc<UserStore>>,
+    allow_upload: bool,
+    upload_limit: u64,
+    select_limit: u64,
+    etags: Etags,
+    compression: bool,
+    mime: MimeTypes,
+    jail: Jail,
+    filter: Filter,
+    max_entries: usize,
+    search_limit: usize,
+    search_timeout: Duration,
+    // 启用 --index 时的文件索引
+    index: Option<Index>,
+    // 文件夹递归大小统计，--dir-size-budget 为 0 时为 None
+    dir_sizes: Option<DirSizes>,
+    digests: Digests,
+    // 正在写入的断点续传上传 ID
+    active_uploads: Arc<Mutex<HashSet<String>>>,
 }
 
-// 最大缓存文件大小 (1MB)
-const MAX_CACHE_FILE_SIZE: u64 = 1024 * 1024;
+impl AppState {
+    // 将 URL 中的相对路径解析为磁盘路径
+    // 只有一个根目录时直接挂载在 /files/ 下, 多个根目录时第一段为别名
+    fn resolve_path(&self, relative: &std::path::Path) -> Option<PathBuf> {
+        if let [root] = self.roots.as_slice() {
+            return Some(root.path.join(relative));
+        }
+
+        let mut components = relative.components();
+        let alias = components.next()?.as_os_str();
+        self.roots
+            .iter()
+            .find(|root| alias == root.alias.as_str())
+            .map(|root| root.path.join(components.as_path()))
+    }
+
+    // 检查路径安全性并解析为磁盘路径，下载、上传和打包共用同一套规则
+    fn checked_path(&self, path: &str, ip: IpAddr) -> Result<PathBuf, StatusCode> {
+        let Some(relative) = jail::relative_path(path) else {
+            error!(ip = %ip, "安全问题: 路径包含目录穿越: {}", path);
+            return Err(StatusCode::BAD_REQUEST);
+        };
+
+        let full_path = self.resolve_path(&relative).ok_or_else(|| {
+            error!(ip = %ip, "Unknown shared root: {}", path);
+            StatusCode::NOT_FOUND
+        })?;
+
+        if !self.jail.permits(&full_path) {
+            error!(ip = %ip, "安全问题: 路径经过不允许的符号链接: {}", path);
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        // 被过滤的文件按不存在处理，不暴露文件是否存在
+        if !self.filter.permits(&full_path, full_path.is_dir()) {
+            error!(ip = %ip, "Filtered path requested: {}", path);
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(full_path)
+    }
+}
+
+// 根据命令行参数生成根目录列表，别名取目录名，重名时追加序号
+fn build_roots(paths: &[PathBuf]) -> Result<Vec<SharedRoot>> {
+    let paths = if paths.is_empty() {
+        vec![std::env::current_dir().context("Failed to get current working directory")?]
+    } else {
+        paths.to_vec()
+    };
+
+    let mut roots: Vec<SharedRoot> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve directory {:?}", path))?;
+        if !path.is_dir() {
+            return Err(anyhow!("Not a directory: {:?}", path));
+        }
+
+        let base = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("root")
+            .to_string();
+        let mut alias = base.clone();
+        let mut n = 2;
+        while roots.iter().any(|root| root.alias == alias) {
+            alias = format!("{}-{}", base, n);
+            n += 1;
+        }
+
+        roots.push(SharedRoot { alias, path });
+    }
+
+    Ok(roots)
+}
+
+// 缓存统计的输出间隔
+const CACHE_STATS_INTERVAL: Duration = Duration::from_secs(60);
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -91,10 +334,9 @@ async fn main() -> Result<()> {
     println!("🔗 {}", PKG_REPOSITORY);
     println!("----------------------------------------");
 
-    // 获取工作目录作为根目录
-    let root_dir = std::env::current_dir()
-        .context("Failed to get current working directory")?;
-    
+    // 获取要分享的根目录，未指定时使用工作目录
+    let roots = build_roots(&args.paths)?;
+
     // 创建作者信息
     let author = Author {
         name: PKG_AUTHORS.split(',').next().unwrap_or("文件分享工具").trim().to_string(),
@@ -103,20 +345,133 @@ async fn main() -> Result<()> {
         github: Some(PKG_REPOSITORY.to_string()),
     };
 
-    // 创建缓存
-    let cache = Cache::new(100); // 缓存最多100个文件
+    // 创建缓存，按字节数限制容量；指定 --cache-ttl 时条目到期后重新读取
+    let cache = if args.no_cache {
+        FileCache::new(0, 0, None)
+    } else {
+        FileCache::new(
+            args.cache_max_bytes,
+            args.cache_max_file_size,
+            args.cache_ttl.map(Duration::from_secs),
+        )
+    };
+    cache.spawn_stats_logger(CACHE_STATS_INTERVAL);
     
+    // 访问令牌: --token <TOKEN> 使用指定值, 仅 --token 时随机生成
+    let token = args
+        .token
+        .map(|token| token.unwrap_or_else(auth::generate_token))
+        .map(Arc::from);
+
+    // Basic 认证账号
+    let users = args
+        .auth_file
+        .as_deref()
+        .map(UserStore::load)
+        .transpose()?
+        .map(Arc::new);
+
+    // 符号链接策略，默认只允许指向共享目录内部的符号链接
+    let symlink_policy = if args.follow_symlinks {
+        SymlinkPolicy::Follow
+    } else if args.no_symlinks {
+        SymlinkPolicy::Deny
+    } else {
+        SymlinkPolicy::Jailed
+    };
+    let jail = Jail::new(roots.iter().map(|root| root.path.clone()).collect(), symlink_policy);
+
+    // 隐藏文件和 --exclude / --include 过滤
+    let filter = Filter::new(
+        roots.iter().map(|root| root.path.clone()).collect(),
+        FilterOptions {
+            exclude: args.exclude.clone(),
+            include: args.include.clone(),
+            hidden: args.hidden,
+            gitignore: args.gitignore,
+        },
+    )?;
+
+    // 文件夹大小统计
+    let dir_sizes = (args.dir_size_budget > 0).then(|| {
+        DirSizes::new(Duration::from_millis(args.dir_size_budget), Duration::from_secs(args.dir_size_ttl))
+    });
+
+    // 文件索引在后台建立，建好之前直接读取磁盘; 文件变化时同时使文件夹大小的统计结果过期
+    let index = if args.index {
+        Some(Index::start(roots.iter().map(|root| root.path.clone()).collect(), dir_sizes.clone())?)
+    } else {
+        None
+    };
+
     let state = AppState {
-        root_dir: Arc::new(root_dir),
+        roots: Arc::new(roots),
         author,
         cache,
+        token,
+        users,
+        allow_upload: args.allow_upload,
+        upload_limit: args.upload_limit,
+        active_uploads: Arc::new(Mutex::new(HashSet::new())),
+        select_limit: args.select_limit,
+        etags: Etags::new(args.etag),
+        compression: !args.no_compression,
+        mime: MimeTypes::new(args.mime.clone()),
+        jail,
+        filter,
+        max_entries: args.max_entries,
+        search_limit: args.search_limit,
+        search_timeout: Duration::from_secs(args.search_timeout),
+        index,
+        dir_sizes,
+        digests: Digests::default(),
     };
 
+    // 启用上传时目录路径同时接受 POST
+    let mut root_route = get(list_files);
+    let mut files_route = get(serve_file);
+    if args.allow_upload {
+        let body_limit = DefaultBodyLimit::max(usize::try_from(args.upload_limit).unwrap_or(usize::MAX));
+        root_route = root_route.post(upload::upload_root).layer(body_limit);
+        files_route = files_route.post(upload::upload_files).layer(body_limit);
+    }
+
     // 构建应用程序
-    let app = Router::new()
-        .route("/", get(list_files))
+    let mut app = Router::new()
+        .route("/", root_route)
         // 使用 {*path} 来捕获所有路径段，包括嵌套路径
-        .route("/files/{*path}", get(serve_file))
+        .route("/files/{*path}", files_route)
+        // 目录打包下载 (?format=zip|tar.gz)，POST 为打包下载勾选的文件
+        .route("/archive", get(archive::archive_root).post(archive::archive_selected))
+        .route("/archive/{*path}", get(archive::archive_dir))
+        // 递归搜索文件名 (?q=&mode=substring|glob|regex&path=)
+        .route("/search", get(search::search))
+        // 服务器状态 (JSON)，包括文件索引的状态
+        .route("/status", get(index::status));
+
+    // 断点续传: POST 的路径是目标目录，HEAD/PATCH/DELETE 的路径是上传 ID
+    if args.allow_upload {
+        app = app
+            .route("/uploads", post(tus::create_root))
+            .route(
+                "/uploads/{*path}",
+                post(tus::create)
+                    .head(tus::status)
+                    .patch(tus::append)
+                    .delete(tus::terminate),
+            );
+    }
+
+    // 按 Accept-Encoding 压缩文本类响应，压缩后的 ETag 改为弱 ETag
+    if state.compression {
+        app = app
+            .layer(compression::layer())
+            .layer(middleware::map_response(compression::adjust_encoded));
+    }
+
+    let app = app
+        // 所有路由都需要通过令牌或 Basic 认证
+        .layer(middleware::from_fn_with_state(state.clone(), auth::require_auth))
         .layer(TraceLayer::new_for_http())
         .layer(ConcurrencyLimitLayer::new(64)) // 限制最大并发请求数为64
         .with_state(state.clone()); // https://github.com/n-WN/share_these/blob/80c267ed15729df5daadb4b480e05cf120d3abc7/src/main.rs#L135
@@ -131,9 +486,32 @@ async fn main() -> Result<()> {
     let display_host = if args.host == "0.0.0.0" { "localhost" } else { &args.host };
     info!("Server running at http://{}:{}", display_host, args.port);
     
-    // 统一使用state.root_dir而不是单独打印root_dir
-    println!("项目根目录: {}", state.root_dir.display());
-    println!("访问地址: http://{}:{}", display_host, args.port);
+    // 统一使用state.roots而不是单独打印目录
+    if let [root] = state.roots.as_slice() {
+        println!("项目根目录: {}", root.path.display());
+    } else {
+        for root in state.roots.iter() {
+            println!("共享目录: /files/{} -> {}", root.alias, root.path.display());
+        }
+    }
+    if state.cache.is_enabled() {
+        println!("小文件缓存: 上限 {}, 单个文件不超过 {}", format_size(args.cache_max_bytes), format_size(args.cache_max_file_size));
+    } else {
+        println!("小文件缓存: 已禁用");
+    }
+    if !state.compression {
+        println!("响应压缩: 已禁用");
+    }
+    if state.allow_upload {
+        println!("上传: 已启用 (单次上限 {})", format_size(args.upload_limit));
+    }
+    if let Some(users) = state.users.as_deref() {
+        println!("Basic 认证: 已加载 {} 个账号", users.user_count());
+    }
+    match state.token.as_deref() {
+        Some(token) => println!("访问地址: http://{}:{}/?token={}", display_host, args.port, token),
+        None => println!("访问地址: http://{}:{}", display_host, args.port),
+    }
     println!("按 Ctrl+C 停止服务");
 
     axum::serve(
@@ -150,16 +528,48 @@ async fn main() -> Result<()> {
 async fn list_files(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
 ) -> Response {
-    let dir = &*state.root_dir;
+    // 多个根目录时列出所有挂载点
+    let listing = if let [root] = state.roots.as_slice() {
+        read_directory(&root.path, None, &state).await
+    } else {
+        let mut entries = Vec::with_capacity(state.roots.len());
+        for root in state.roots.iter() {
+            let metadata = fs::metadata(&root.path).await.ok();
+            entries.push(Entry {
+                name: root.alias.clone(),
+                path: root.alias.clone(),
+                kind: EntryKind::Dir,
+                size: 0,
+                mtime: metadata.as_ref().and_then(unix_mtime),
+                mime: None,
+                files: None,
+            });
+        }
+        Ok((entries, false))
+    };
+    let json = listing::wants_json(&headers, &query);
+    let options = ListingOptions::from_request(&query, &headers);
 
-    match read_directory(dir, None).await {
-        Ok((folders, files)) => {
-            info!(ip = %addr.ip(), "File list requested for root directory");
-            render_file_list(folders, files, Some("/"), &state.author)
+    match listing {
+        Ok((entries, truncated)) => {
+            info!(ip = %addr.ip(), user = %user, "File list requested for root directory");
+            let page = paginate(&state, entries, truncated, &options).await;
+            if json {
+                return listing::render_json("", &page);
+            }
+            let mut response = render_file_list(&page, Some("/"), &options, &state.author, state.allow_upload);
+            options.remember(&mut response);
+            response
         }
         Err(e) => {
             error!(ip = %addr.ip(), "Failed to read directory: {:#}", e);
+            if json {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
             Html(format!(
                 r#"<html><body><h1>Error</h1><p>{:#}</p></body></html>"#,
                 e
@@ -169,20 +579,32 @@ async fn list_files(
     }
 }
 
+// 排序并取出当前页，只统计当前页中文件夹的大小; 按大小排序时需要先统计所有文件夹
+async fn paginate(state: &AppState, mut entries: Vec<Entry>, truncated: bool, options: &ListingOptions) -> Page {
+    if options.sort.key == SortKey::Size {
+        dirsize::fill(state, &mut entries).await;
+        options.sort.apply(&mut entries);
+        return options.pagination.apply(entries, truncated);
+    }
+    options.sort.apply(&mut entries);
+    let mut page = options.pagination.apply(entries, truncated);
+    dirsize::fill(state, &mut page.entries).await;
+    page
+}
+
 // 提供文件下载
 async fn serve_file(
     Path(path): Path<String>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<HashMap<String, String>>,
     headers: HeaderMap,
 ) -> Response {
-    // 检查路径安全性
-    if path.contains("..") {
-        error!(ip = %addr.ip(), "安全问题: 路径包含'..'序列: {}", path);
-        return StatusCode::BAD_REQUEST.into_response();
-    }
-
-    let full_path = state.root_dir.join(&path);
+    let full_path = match state.checked_path(&path, addr.ip()) {
+        Ok(full_path) => full_path,
+        Err(status) => return status.into_response(),
+    };
 
     // 检查文件是否存在
     if !full_path.exists() {
@@ -190,15 +612,31 @@ async fn serve_file(
         return StatusCode::NOT_FOUND.into_response();
     }
 
+    // ?hash= 返回文件的摘要，目录返回所有文件的摘要清单
+    if query.contains_key("hash") {
+        return digest::serve(&full_path, &path, &query, &headers, addr.ip().to_string(), &user, &state).await;
+    }
+
     // 如果是目录，则显示目录内容
     if full_path.is_dir() {
-        match read_directory(&full_path, Some(&path)).await {
-            Ok((folders, files)) => {
-                info!(ip = %addr.ip(), "Directory listing for: {}", path);
-                render_file_list(folders, files, Some(&path), &state.author)
+        let json = listing::wants_json(&headers, &query);
+        let options = ListingOptions::from_request(&query, &headers);
+        match read_directory(&full_path, Some(&path), &state).await {
+            Ok((entries, truncated)) => {
+                info!(ip = %addr.ip(), user = %user, "Directory listing for: {}", path);
+                let page = paginate(&state, entries, truncated, &options).await;
+                if json {
+                    return listing::render_json(&path, &page);
+                }
+                let mut response = render_file_list(&page, Some(&path), &options, &state.author, state.allow_upload);
+                options.remember(&mut response);
+                response
             }
             Err(e) => {
                 error!(ip = %addr.ip(), "Failed to read directory: {:#}", e);
+                if json {
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
                 Html(format!(
                     r#"<html><body><h1>Error</h1><p>{:#}</p></body></html>"#,
                     e
@@ -207,86 +645,221 @@ async fn serve_file(
             }
         }
     } else {
-        // 检查缓存 - 使用await等待Future完成
-        if let Some(cached_data) = state.cache.get(&path).await {
-            info!(ip = %addr.ip(), "Serving cached file: {:?}", full_path);
-            return Response::builder()
-                .status(StatusCode::OK)
-                .header(CONTENT_TYPE, determine_content_type(&full_path))
-                .header(CONTENT_LENGTH, cached_data.len().to_string())
-                .body(Body::from(cached_data))
-                .unwrap()
-                .into_response();
+        let mut response = serve_regular_file(&full_path, &path, &headers, &addr, &user, &state).await;
+        // 成功的响应带上 Content-Disposition，?download=1 强制下载，?inline=1 强制打开
+        if response.status().is_success() {
+            let file_name = full_path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+            response
+                .headers_mut()
+                .insert(CONTENT_DISPOSITION, Disposition::from_query(&query).header_value(&file_name));
+            // 允许上传时文件内容来自其他用户，上传的 HTML / SVG 在沙箱中打开，
+            // 不能在页面同源下执行脚本 (读取令牌 cookie 或以当前用户身份发起请求)
+            if state.allow_upload {
+                let headers = response.headers_mut();
+                headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+                headers.insert(CONTENT_SECURITY_POLICY, HeaderValue::from_static("sandbox"));
+            }
         }
+        response
+    }
+}
 
-        // 流式传输文件内容
-        match stream_file(&full_path, &path, &headers, addr.ip().to_string(), &state.cache).await {
-            Ok(response) => response,
-            Err(e) => {
-                error!(ip = %addr.ip(), "Failed to stream file: {:?}, error: {:#}", full_path, e);
-                match e.downcast_ref::<std::io::Error>() {
-                    Some(io_err) if io_err.kind() == std::io::ErrorKind::PermissionDenied => {
-                        StatusCode::FORBIDDEN.into_response()
-                    },
-                    Some(io_err) if io_err.kind() == std::io::ErrorKind::NotFound => {
-                        StatusCode::NOT_FOUND.into_response()
-                    },
-                    _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+// 发送普通文件: 预压缩文件、缓存或流式传输
+async fn serve_regular_file(
+    full_path: &PathBuf,
+    path: &str,
+    headers: &HeaderMap,
+    addr: &SocketAddr,
+    user: &AuthUser,
+    state: &AppState,
+) -> Response {
+    // 客户端接受压缩且存在预压缩的 .br / .gz 文件时直接发送，Range 请求始终使用原文件
+    let sidecar = if state.compression
+        && !headers.contains_key(RANGE)
+        && state.mime.by_extension(full_path).is_some_and(|mime| compression::is_compressible(&mime))
+    {
+        // 预压缩文件与原文件一样需要符合符号链接策略和过滤规则
+        compression::precompressed(full_path, headers, |sidecar| {
+            state.jail.permits(sidecar) && state.filter.permits(sidecar, false)
+        })
+        .await
+    } else {
+        None
+    };
+
+    let result = match sidecar {
+        Some((sidecar, encoding)) => {
+            serve_precompressed(full_path, &sidecar, encoding, headers, addr.ip().to_string(), user, state).await
+        }
+        None => {
+            // 检查缓存 - 使用await等待Future完成 (范围请求不走缓存)
+            let cached = if headers.contains_key(RANGE) { None } else { state.cache.get(path).await };
+            if let Some(cached) = cached {
+                // 缓存命中时校验磁盘上的文件是否已被修改，修改过则丢弃缓存重新读取
+                match fs::metadata(full_path).await.ok().filter(|metadata| cached.is_fresh(metadata)) {
+                    Some(_) => return serve_cached(&cached, full_path, headers, addr, user),
+                    None => {
+                        info!(ip = %addr.ip(), user = %user, "Cached file changed on disk, invalidating: {:?}", full_path);
+                        state.cache.invalidate(path).await;
+                    }
                 }
             }
+
+            // 流式传输文件内容
+            stream_file(full_path, path, headers, addr.ip().to_string(), user, state).await
+        }
+    };
+
+    match result {
+        Ok(response) => response,
+        Err(e) => {
+            error!(ip = %addr.ip(), "Failed to stream file: {:?}, error: {:#}", full_path, e);
+            match e.downcast_ref::<std::io::Error>() {
+                Some(io_err) if io_err.kind() == std::io::ErrorKind::PermissionDenied => {
+                    StatusCode::FORBIDDEN.into_response()
+                },
+                Some(io_err) if io_err.kind() == std::io::ErrorKind::NotFound => {
+                    StatusCode::NOT_FOUND.into_response()
+                },
+                _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
         }
     }
 }
 
+// 返回缓存中的文件内容，使用缓存时生成的验证器处理条件请求
+fn serve_cached(
+    cached: &CachedFile,
+    full_path: &PathBuf,
+    headers: &HeaderMap,
+    addr: &SocketAddr,
+    user: &AuthUser,
+) -> Response {
+    if cached.validators.is_not_modified(headers) {
+        info!(ip = %addr.ip(), user = %user, "Not modified: {:?}", full_path);
+        return cached.validators.not_modified();
+    }
+
+    info!(ip = %addr.ip(), user = %user, "Serving cached file: {:?}", full_path);
+    cached.response()
+}
+
+// 发送预压缩文件，内容类型沿用原文件，验证器由预压缩文件生成
+async fn serve_precompressed(
+    original: &std::path::Path,
+    sidecar: &PathBuf,
+    encoding: &'static str,
+    headers: &HeaderMap,
+    client_ip: String,
+    user: &AuthUser,
+    state: &AppState,
+) -> Result<Response> {
+    let metadata = fs::metadata(sidecar).await
+        .with_context(|| format!("Failed to get metadata for {:?}", sidecar))?;
+
+    let validators = state.etags.validators(sidecar, &metadata).await?;
+    if validators.is_not_modified(headers) {
+        info!(ip = %client_ip, user = %user, "Not modified: {:?}", sidecar);
+        return Ok(validators.not_modified());
+    }
+
+    info!(ip = %client_ip, user = %user, "Serving precompressed file: {:?}", sidecar);
+    let file = File::open(sidecar).await
+        .with_context(|| format!("Failed to open file {:?}", sidecar))?;
+    let body = Body::from_stream(ReaderStream::with_capacity(file, 8 * 1024));
+
+    let mut response_headers = HeaderMap::new();
+    if let Ok(content_type) = HeaderValue::from_str(&state.mime.detect(original).await) {
+        response_headers.insert(CONTENT_TYPE, content_type);
+    }
+    response_headers.insert(CONTENT_LENGTH, HeaderValue::from(metadata.len()));
+    response_headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    response_headers.insert(VARY, HeaderValue::from_static("accept-encoding"));
+    validators.apply(&mut response_headers);
+
+    Ok((StatusCode::OK, response_headers, body).into_response())
+}
+
 // 流式传输文件
 async fn stream_file(
     path: &PathBuf, 
     cache_key: &str,
     headers: &HeaderMap, 
     client_ip: String, 
-    cache: &Cache<String, Vec<u8>>
+    user: &AuthUser,
+    state: &AppState,
 ) -> Result<Response> {
     // 获取文件元数据
     let metadata = fs::metadata(path).await
         .with_context(|| format!("Failed to get metadata for {:?}", path))?;
+
+    // 生成验证器，文件未修改时直接返回 304
+    let validators = state.etags.validators(path, &metadata).await?;
+    if validators.is_not_modified(headers) {
+        info!(ip = %client_ip, user = %user, "Not modified: {:?}", path);
+        return Ok(validators.not_modified());
+    }
+
+    // If-Range 不匹配时文件已变化，忽略 Range 返回完整文件
+    let range_header = headers.get(RANGE).filter(|_| validators.if_range_matches(headers));
+
+    // 确定内容类型，扩展名未知时嗅探文件头
+    let content_type = state.mime.detect(path).await;
+
+    // 完整请求的小文件读入内存并缓存，之后的请求共享同一份内容
+    if range_header.is_none() && state.cache.accepts(metadata.len()) {
+        info!(ip = %client_ip, user = %user, "Caching small file: {:?} ({} bytes)", path, metadata.len());
+        let mut cached = load_cached_file(path, &content_type, validators).await?;
+        // 内容已在内存中，顺便计算 SHA-256 摘要
+        let digest = state.digests.of_bytes(path, &metadata, digest::Algorithm::Sha256, &cached.body).await;
+        digest::apply_sha256(&digest, &mut cached.headers);
+        let cached = Arc::new(cached);
+        state.cache.insert(cache_key.to_string(), cached.clone()).await;
+        return Ok(cached.response());
+    }
+
+    let mut response = send_file(path, range_header, &metadata, &content_type, client_ip, user).await?;
+    validators.apply(response.headers_mut());
+    // 完整下载时带上已计算过的 SHA-256 摘要，不为此推迟响应
+    // 还没有摘要时在后台计算，文件未修改的情况下之后的完整下载都会带上
+    if response.status() == StatusCode::OK {
+        match state.digests.cached(path, &metadata, digest::Algorithm::Sha256).await {
+            Some(digest) => digest::apply_sha256(&digest, response.headers_mut()),
+            None => state.digests.spawn(path, &metadata, digest::Algorithm::Sha256),
+        }
+    }
+    Ok(response)
+}
+
+// 根据 Range 头返回完整文件或部分内容
+async fn send_file(
+    path: &PathBuf,
+    range_header: Option<&axum::http::HeaderValue>,
+    metadata: &std::fs::Metadata,
+    content_type: &str,
+    client_ip: String,
+    user: &AuthUser,
+) -> Result<Response> {
     let file_size = metadata.len();
-    
-    // 确定内容类型
-    let content_type = determine_content_type(path);
-    
+
     // 检查是否是范围请求
-    if let Some(range_header) = headers.get(RANGE) {
-        return handle_range_request(path, range_header, file_size, content_type, client_ip).await;
+    if let Some(range_header) = range_header {
+        match parse_range(range_header, file_size) {
+            Some(ranges) if ranges.is_empty() => {
+                info!(ip = %client_ip, user = %user, "Range not satisfiable: {:?} {:?}/{}", path, range_header, file_size);
+                return Ok(range_not_satisfiable(file_size));
+            }
+            Some(ranges) => {
+                return handle_range_request(path, ranges, file_size, content_type, client_ip, user).await;
+            }
+            // 无效的 Range 头按规范忽略，返回完整文件
+            None => info!(ip = %client_ip, user = %user, "Ignoring invalid range header: {:?}", range_header),
+        }
     }
     
     // 标准请求 - 流式传输整个文件
-    info!(ip = %client_ip, "Streaming full file: {:?}", path);
-    
-    // 如果文件小于阈值，先读入内存然后缓存并返回
-    if file_size <= MAX_CACHE_FILE_SIZE {
-        // 添加日志，记录哪些文件被缓存
-        info!(ip = %client_ip, "Caching small file: {:?} ({} bytes)", path, file_size);
-        
-        let mut file = File::open(path).await
-            .with_context(|| format!("Failed to open file {:?}", path))?;
-        
-        let mut buffer = Vec::with_capacity(file_size as usize);
-        file.read_to_end(&mut buffer).await
-            .with_context(|| format!("Failed to read file {:?}", path))?;
-        
-        // 缓存文件内容
-        cache.insert(cache_key.to_string(), buffer.clone()).await;
-        
-        // 设置响应头
-        let mut response_headers = HeaderMap::new();
-        response_headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
-        response_headers.insert(CONTENT_LENGTH, file_size.to_string().parse().unwrap());
-        response_headers.insert(ACCEPT_RANGES, "bytes".parse().unwrap());
-        
-        return Ok((StatusCode::OK, response_headers, Body::from(buffer)).into_response());
-    }
+    info!(ip = %client_ip, user = %user, "Streaming full file: {:?}", path);
     
-    // 对于大文件，使用流式传输
     let file = File::open(path).await
         .with_context(|| format!("Failed to open file {:?}", path))?;
     
@@ -304,76 +877,163 @@ async fn stream_file(
     Ok((StatusCode::OK, response_headers, body).into_response())
 }
 
-// 处理HTTP Range请求
+// 读入整个小文件，并预先生成响应头
+async fn load_cached_file(path: &std::path::Path, content_type: &str, validators: Validators) -> Result<CachedFile> {
+    let data = fs::read(path).await
+        .with_context(|| format!("Failed to read file {:?}", path))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(data.len()));
+    headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    validators.apply(&mut headers);
+
+    Ok(CachedFile {
+        body: Bytes::from(data),
+        headers,
+        validators,
+    })
+}
+
+// 单次请求最多接受的范围数量，超过时忽略 Range 头返回完整文件
+const MAX_RANGES: usize = 32;
+
+// 解析 Range 头 (RFC 9110 14.1.2)，返回闭区间列表
+// None 表示语法无效或不支持的单位，应忽略 Range 头；空列表表示所有范围都无法满足
+fn parse_range(range_header: &axum::http::HeaderValue, file_size: u64) -> Option<Vec<(u64, u64)>> {
+    let range_str = range_header.to_str().ok()?;
+    let specs = range_str.trim().strip_prefix("bytes=")?;
+
+    let mut ranges = Vec::new();
+    let mut parsed = 0;
+    for spec in specs.split(',') {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            continue;
+        }
+        parsed += 1;
+        let (first, last) = spec.split_once('-')?;
+        let (first, last) = (first.trim(), last.trim());
+
+        if first.is_empty() {
+            // 后缀范围: bytes=-500 表示最后 500 个字节
+            let suffix = last.parse::<u64>().ok()?;
+            if suffix > 0 && file_size > 0 {
+                ranges.push((file_size.saturating_sub(suffix), file_size - 1));
+            }
+            continue;
+        }
+
+        let start = first.parse::<u64>().ok()?;
+        let end = if last.is_empty() {
+            u64::MAX
+        } else {
+            last.parse::<u64>().ok()?
+        };
+        if start > end {
+            return None;
+        }
+        // 起始位置超出文件大小的范围无法满足
+        if start < file_size {
+            ranges.push((start, min(end, file_size - 1)));
+        }
+    }
+
+    // 没有任何 range-spec (如 "bytes=") 的 Range 头同样无效
+    if parsed == 0 || ranges.len() > MAX_RANGES {
+        return None;
+    }
+    Some(ranges)
+}
+
+// 416 响应，Content-Range 中给出文件实际大小
+fn range_not_satisfiable(file_size: u64) -> Response {
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(CONTENT_RANGE, format!("bytes */{}", file_size).parse().unwrap());
+    response_headers.insert(ACCEPT_RANGES, "bytes".parse().unwrap());
+    (StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response()
+}
+
+// 打开文件并定位到 start，返回只读取 len 个字节的流
+async fn open_range(path: &std::path::Path, start: u64, len: u64) -> std::io::Result<ReaderStream<BoundedReader<File>>> {
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    // 创建自定义流以限制读取的字节数，设置8KB缓冲区
+    Ok(ReaderStream::with_capacity(BoundedReader::new(file, len), 8 * 1024))
+}
+
+// 处理HTTP Range请求: 单个范围返回 206，多个范围返回 multipart/byteranges
 async fn handle_range_request(
     path: &PathBuf,
-    range_header: &axum::http::HeaderValue,
+    ranges: Vec<(u64, u64)>,
     file_size: u64,
-    content_type: &'static str,
-    client_ip: String
+    content_type: &str,
+    client_ip: String,
+    user: &AuthUser,
 ) -> Result<Response> {
-    // 解析Range头 (格式: "bytes=start-end")
-    let range_str = range_header.to_str().map_err(|_| anyhow!("Invalid range header"))?;
-    
-    if !range_str.starts_with("bytes=") {
-        return Err(anyhow!("Unsupported range unit"));
-    }
-    
-    let range_parts: Vec<&str> = range_str["bytes=".len()..].split('-').collect();
-    if range_parts.len() != 2 {
-        return Err(anyhow!("Invalid range format"));
-    }
-    
-    // 解析start和end位置
-    let start = if range_parts[0].is_empty() { 
-        0 
-    } else { 
-        range_parts[0].parse::<u64>().map_err(|_| anyhow!("Invalid range start"))? 
-    };
-    
-    let end = if range_parts[1].is_empty() { 
-        file_size - 1 
-    } else { 
-        range_parts[1].parse::<u64>().map_err(|_| anyhow!("Invalid range end"))? 
-    };
-    
-    // 验证范围有效性并提供更详细的错误信息
-    if start > end {
-        return Err(anyhow!("Invalid range: start ({}) > end ({})", start, end));
-    }
-    
-    if start >= file_size {
-        return Err(anyhow!("Range start ({}) exceeds file size ({})", start, file_size));
+    if let [(start, end)] = ranges[..] {
+        let content_length = end - start + 1;
+        info!(ip = %client_ip, user = %user, "Range request: {:?}, bytes {}-{}/{}", path, start, end, file_size);
+
+        let reader_stream = open_range(path, start, content_length)
+            .await
+            .with_context(|| format!("Failed to open {:?} at position {}", path, start))?;
+        let body = Body::from_stream(reader_stream);
+
+        // 设置响应头
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+        response_headers.insert(CONTENT_LENGTH, content_length.to_string().parse().unwrap());
+        response_headers.insert(
+            CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, file_size).parse().unwrap()
+        );
+        response_headers.insert(ACCEPT_RANGES, "bytes".parse().unwrap());
+
+        return Ok((StatusCode::PARTIAL_CONTENT, response_headers, body).into_response());
     }
-    
-    // 范围长度和实际结束位置
-    let end = min(end, file_size - 1);
-    let content_length = end - start + 1;
-    
-    info!(ip = %client_ip, "Range request: {:?}, bytes {}-{}/{}", path, start, end, file_size);
-    
-    // 打开文件并跳到起始位置
-    let mut file = File::open(path).await
+
+    info!(ip = %client_ip, user = %user, "Multi-range request: {:?}, {} ranges/{}", path, ranges.len(), file_size);
+
+    // 先确认文件可以打开，避免返回 206 之后才发现错误
+    File::open(path).await
         .with_context(|| format!("Failed to open file {:?}", path))?;
-    
-    file.seek(SeekFrom::Start(start)).await
-        .with_context(|| format!("Failed to seek to position {} in file {:?}", start, path))?;
-    
-    // 创建自定义流以限制读取的字节数
-    let bounded_file = BoundedReader::new(file, content_length);
-    let reader_stream = ReaderStream::with_capacity(bounded_file, 8 * 1024); // 设置8KB缓冲区
-    let body = Body::from_stream(reader_stream);
-    
-    // 设置响应头
+
+    // multipart/byteranges: 每个部分带自己的 Content-Type 和 Content-Range
+    let boundary = auth::generate_token();
+    let part_headers: Vec<Bytes> = ranges
+        .iter()
+        .enumerate()
+        .map(|(idx, (start, end))| {
+            Bytes::from(format!(
+                "{}--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                if idx == 0 { "" } else { "\r\n" },
+                boundary, content_type, start, end, file_size
+            ))
+        })
+        .collect();
+    let closing = Bytes::from(format!("\r\n--{}--\r\n", boundary));
+
+    let content_length = part_headers.iter().map(|h| h.len() as u64).sum::<u64>()
+        + ranges.iter().map(|(start, end)| end - start + 1).sum::<u64>()
+        + closing.len() as u64;
+
+    let path = path.clone();
+    let parts = part_headers.into_iter().zip(ranges).map(move |(header, (start, end))| {
+        let path = path.clone();
+        let data = stream::once(async move { open_range(&path, start, end - start + 1).await }).try_flatten();
+        stream::once(future::ready(Ok(header))).chain(data)
+    });
+    let body = Body::from_stream(stream::iter(parts).flatten().chain(stream::once(future::ready(Ok(closing)))));
+
     let mut response_headers = HeaderMap::new();
-    response_headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
-    response_headers.insert(CONTENT_LENGTH, content_length.to_string().parse().unwrap());
     response_headers.insert(
-        CONTENT_RANGE, 
-        format!("bytes {}-{}/{}", start, end, file_size).parse().unwrap()
+        CONTENT_TYPE,
+        format!("multipart/byteranges; boundary={}", boundary).parse().unwrap()
     );
+    response_headers.insert(CONTENT_LENGTH, content_length.to_string().parse().unwrap());
     response_headers.insert(ACCEPT_RANGES, "bytes".parse().unwrap());
-    
+
     Ok((StatusCode::PARTIAL_CONTENT, response_headers, body).into_response())
 }
 
@@ -420,72 +1080,160 @@ impl<R: AsyncRead + Unpin> AsyncRead for BoundedReader<R> {
     }
 }
 
-// 辅助函数：确定内容类型
-fn determine_content_type(path: &PathBuf) -> &'static str {
-    match path.extension().and_then(|e| e.to_str()) {
-        Some("html") | Some("htm") => "text/html",
-        Some("css") => "text/css",
-        Some("js") => "application/javascript",
-        Some("json") => "application/json",
-        Some("png") => "image/png",
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("gif") => "image/gif",
-        Some("svg") => "image/svg+xml",
-        Some("pdf") => "application/pdf",
-        Some("txt") | Some("md") => "text/plain",
-        _ => "application/octet-stream",
-    }
-}
+// 同时读取元数据的条目数
+const METADATA_CONCURRENCY: usize = 64;
 
-// 辅助函数：读取目录内容
+// 辅助函数：读取目录内容，返回的条目未排序
+// 可见条目超过 --max-entries 时只返回前面部分，第二个返回值为 true
 async fn read_directory(
     dir: &PathBuf,
     path_prefix: Option<&String>,
-) -> Result<(Vec<(String, String, u64)>, Vec<(String, String, u64)>)> {
-    let mut entries = fs::read_dir(dir)
+    state: &AppState,
+) -> Result<(Vec<Entry>, bool)> {
+    let dir_filter = state.filter.dir(dir);
+
+    // 索引就绪时直接使用索引，否则读取磁盘
+    let (nodes, truncated) = match state.index.as_ref().and_then(|index| index.children(dir)) {
+        Some(children) => {
+            let mut nodes = Vec::new();
+            let mut truncated = false;
+            for node in children.iter() {
+                let Some(node) = follow_symlink(dir, node.clone(), state).await else {
+                    continue;
+                };
+                if !dir_filter.permits(&node.name, node.is_dir) {
+                    continue;
+                }
+                if nodes.len() >= state.max_entries {
+                    truncated = true;
+                    break;
+                }
+                nodes.push(node);
+            }
+            (nodes, truncated)
+        }
+        None => read_nodes(dir, &dir_filter, state).await?,
+    };
+
+    let entries = nodes
+        .into_iter()
+        .map(|node| {
+            let entry_path = dir.join(&node.name);
+            let name = node.name.to_str().unwrap_or("Unknown").to_string();
+
+            // 处理相对路径
+            let relative_path = if let Some(prefix) = path_prefix {
+                format!("{}/{}", prefix, name)
+            } else {
+                name.clone()
+            };
+
+            // 区分文件和文件夹
+            if node.is_dir {
+                Entry {
+                    name,
+                    path: relative_path,
+                    kind: EntryKind::Dir,
+                    size: 0,
+                    mtime: node.modified.and_then(unix_secs),
+                    mime: None,
+                    files: None,
+                }
+            } else {
+                Entry {
+                    mime: state.mime.by_extension(&entry_path),
+                    name,
+                    path: relative_path,
+                    kind: EntryKind::File,
+                    size: node.size,
+                    mtime: node.modified.and_then(unix_secs),
+                    files: None,
+                }
+            }
+        })
+        .collect();
+
+    Ok((entries, truncated))
+}
+
+// 从磁盘读取目录中可见的条目
+async fn read_nodes(dir: &PathBuf, dir_filter: &DirFilter<'_>, state: &AppState) -> Result<(Vec<Node>, bool)> {
+    let mut read_dir = fs::read_dir(dir)
         .await
         .with_context(|| format!("Failed to read directory {:?}", dir))?;
 
-    let mut files = Vec::new();
-    let mut folders = Vec::new();
-
-    while let Some(entry) = entries.next_entry()
+    // 先只收集名称，d_type 通常不需要额外的 stat
+    let mut visible = Vec::new();
+    let mut truncated = false;
+    while let Some(entry) = read_dir.next_entry()
         .await
         .with_context(|| format!("Failed to read directory entry in {:?}", dir))?
     {
-        let metadata = entry.metadata()
-            .await
-            .with_context(|| format!("Failed to read metadata for {:?}", entry.path()))?;
+        // 符号链接需要先确认目标才知道是否为目录
+        let (is_dir, resolved) = match entry.file_type().await {
+            Ok(file_type) if file_type.is_symlink() => {
+                let Ok(metadata) = entry.metadata().await else {
+                    continue;
+                };
+                let Some(node) = follow_symlink(dir, Node::new(entry.file_name(), &metadata), state).await else {
+                    continue;
+                };
+                (node.is_dir, Some(node))
+            }
+            file_type => (file_type.is_ok_and(|t| t.is_dir()), None),
+        };
+        // 跳过隐藏和被排除的条目
+        if !dir_filter.permits(&entry.file_name(), is_dir) {
+            continue;
+        }
+        if visible.len() >= state.max_entries {
+            truncated = true;
+            break;
+        }
+        visible.push((entry, resolved));
+    }
 
-        let entry_path = entry.path();
-        let name = entry_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("Unknown")
-            .to_string();
+    // 并发读取元数据，大目录不必逐个等待
+    let nodes = stream::iter(visible)
+        .map(|(entry, resolved)| async move {
+            if let Some(node) = resolved {
+                return Ok(node);
+            }
+            let metadata = entry.metadata()
+                .await
+                .with_context(|| format!("Failed to read metadata for {:?}", entry.path()))?;
+            Ok::<_, anyhow::Error>(Node::new(entry.file_name(), &metadata))
+        })
+        .buffer_unordered(METADATA_CONCURRENCY)
+        .try_collect()
+        .await?;
 
-        // 处理相对路径
-        let relative_path = if let Some(prefix) = path_prefix {
-            format!("{}/{}", prefix, name)
-        } else {
-            entry_path.strip_prefix(dir)
-                .map(|p| p.to_string_lossy().to_string())
-                .unwrap_or_else(|_| name.clone())
-        };
+    Ok((nodes, truncated))
+}
 
-        // 区分文件和文件夹
-        if metadata.is_dir() {
-            folders.push((name, relative_path, 0));
-        } else {
-            files.push((name, relative_path, metadata.len()));
-        }
+// 符号链接按目标列出: 不符合符号链接策略的不列出，其余使用目标的类型、大小和修改时间
+// 失效的链接保持链接本身的信息
+async fn follow_symlink(dir: &std::path::Path, node: Node, state: &AppState) -> Option<Node> {
+    if !node.symlink {
+        return Some(node);
+    }
+    let path = dir.join(&node.name);
+    if !state.jail.permits(&path) {
+        return None;
+    }
+    match fs::metadata(&path).await {
+        Ok(metadata) => Some(Node { symlink: true, ..Node::new(node.name, &metadata) }),
+        Err(_) => Some(node),
     }
+}
 
-    // 按字母顺序排序
-    folders.sort_by(|a, b| a.0.cmp(&b.0));
-    files.sort_by(|a, b| a.0.cmp(&b.0));
+// 修改时间的 Unix 时间戳 (秒)
+fn unix_mtime(metadata: &std::fs::Metadata) -> Option<u64> {
+    metadata.modified().ok().and_then(unix_secs)
+}
 
-    Ok((folders, files))
+fn unix_secs(time: std::time::SystemTime) -> Option<u64> {
+    time.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs())
 }
 
 // 格式化文件大小
@@ -503,4 +1251,86 @@ pub fn format_size(size: u64) -> String {
     } else {
         format!("{:.1} GB", size as f64 / GB as f64)
     }
-}
\ No newline at end of file
+}
+
+// 解析带单位的大小 (如 512K、100M、1G)，不带单位时为字节
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let (number, unit) = value
+        .find(|c: char| !c.is_ascii_digit())
+        .map(|idx| value.split_at(idx))
+        .unwrap_or((value, ""));
+    let number: u64 = number.parse().map_err(|_| anyhow!("Invalid size: {}", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(anyhow!("Invalid size unit: {}", value)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Size too large: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_does_not_take_following_path() {
+        let args = Args::try_parse_from(["share_these", "--token", "/data"]).unwrap();
+        assert_eq!(args.token, Some(None));
+        assert_eq!(args.paths, [PathBuf::from("/data")]);
+
+        let args = Args::try_parse_from(["share_these", "--token=secret", "/data"]).unwrap();
+        assert_eq!(args.token, Some(Some("secret".to_string())));
+        assert_eq!(args.paths, [PathBuf::from("/data")]);
+
+        let args = Args::try_parse_from(["share_these", "/data"]).unwrap();
+        assert_eq!(args.token, None);
+    }
+
+    fn ranges(header: &str, file_size: u64) -> Option<Vec<(u64, u64)>> {
+        parse_range(&HeaderValue::from_str(header).unwrap(), file_size)
+    }
+
+    #[test]
+    fn parse_range_single() {
+        assert_eq!(ranges("bytes=0-499", 1000), Some(vec![(0, 499)]));
+        assert_eq!(ranges("bytes=500-", 1000), Some(vec![(500, 999)]));
+        assert_eq!(ranges("bytes=900-2000", 1000), Some(vec![(900, 999)]));
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(ranges("bytes=-500", 1000), Some(vec![(500, 999)]));
+        assert_eq!(ranges("bytes=-2000", 1000), Some(vec![(0, 999)]));
+        assert_eq!(ranges("bytes=-0", 1000), Some(vec![]));
+        assert_eq!(ranges("bytes=-10", 0), Some(vec![]));
+    }
+
+    #[test]
+    fn parse_range_multiple() {
+        assert_eq!(ranges("bytes=0-0, -1", 1000), Some(vec![(0, 0), (999, 999)]));
+        assert_eq!(ranges("bytes=0-1,,5-6", 1000), Some(vec![(0, 1), (5, 6)]));
+        let many = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(ranges(&many, 1000), None);
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable() {
+        assert_eq!(ranges("bytes=1000-", 1000), Some(vec![]));
+        assert_eq!(ranges("bytes=0-", 0), Some(vec![]));
+    }
+
+    #[test]
+    fn parse_range_invalid() {
+        assert_eq!(ranges("bytes=", 1000), None);
+        assert_eq!(ranges("bytes= , ", 1000), None);
+        assert_eq!(ranges("items=0-1", 1000), None);
+        assert_eq!(ranges("bytes=5-1", 1000), None);
+        assert_eq!(ranges("bytes=abc", 1000), None);
+        assert_eq!(ranges("bytes=0-1,x-y", 1000), None);
+    }
+}
@@ -0,0 +1,110 @@
+// MIME 类型识别: 命令行覆盖 > 扩展名数据库 > 文件头魔数嗅探
+use anyhow::{anyhow, Result};
+use axum::http::HeaderValue;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+// 嗅探时读取的文件头字节数
+const SNIFF_LEN: usize = 8 * 1024;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+// 内置的覆盖项: Markdown 按纯文本显示，浏览器可以直接预览
+const DEFAULT_OVERRIDES: [(&str, &str); 1] = [("md", "text/plain")];
+
+#[derive(Clone)]
+pub struct MimeTypes {
+    // 扩展名 (小写，不含点) -> MIME 类型
+    overrides: Arc<HashMap<String, String>>,
+}
+
+impl MimeTypes {
+    pub fn new(overrides: Vec<(String, String)>) -> Self {
+        let mut map: HashMap<String, String> = DEFAULT_OVERRIDES
+            .iter()
+            .map(|(ext, mime)| (ext.to_string(), mime.to_string()))
+            .collect();
+        map.extend(overrides);
+        Self { overrides: Arc::new(map) }
+    }
+
+    // 只根据扩展名判断，未知时返回 None
+    pub fn by_extension(&self, path: &Path) -> Option<String> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        let mime = match self.overrides.get(&ext) {
+            Some(mime) => mime.as_str(),
+            None => mime_guess::from_ext(&ext).first_raw()?,
+        };
+        Some(with_charset(mime))
+    }
+
+    // 扩展名未知或没有扩展名时读取文件头嗅探
+    pub async fn detect(&self, path: &Path) -> String {
+        match self.by_extension(path) {
+            Some(mime) => mime,
+            None => with_charset(sniff(path).await),
+        }
+    }
+}
+
+// 根据文件头魔数判断类型，都不匹配时看内容是否为 UTF-8 文本
+async fn sniff(path: &Path) -> &'static str {
+    let mut buffer = Vec::with_capacity(SNIFF_LEN);
+    let read = match File::open(path).await {
+        Ok(file) => file.take(SNIFF_LEN as u64).read_to_end(&mut buffer).await,
+        Err(e) => Err(e),
+    };
+    if read.is_err() || buffer.is_empty() {
+        return OCTET_STREAM;
+    }
+
+    if let Some(kind) = infer::get(&buffer) {
+        return kind.mime_type();
+    }
+    if looks_like_text(&buffer) {
+        "text/plain"
+    } else {
+        OCTET_STREAM
+    }
+}
+
+// 不含 NUL 且是合法 UTF-8 (允许末尾被截断的多字节字符)
+fn looks_like_text(buffer: &[u8]) -> bool {
+    if buffer.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buffer) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+// 文本类型加上 charset=utf-8，已带参数的保持不变
+fn with_charset(mime: &str) -> String {
+    let textual = mime.starts_with("text/")
+        || matches!(
+            mime,
+            "application/json" | "application/javascript" | "application/xml" | "image/svg+xml"
+        );
+    if textual && !mime.contains(';') {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    }
+}
+
+// 解析 --mime 参数: 扩展名=类型，如 log=text/plain
+pub fn parse_override(value: &str) -> Result<(String, String)> {
+    let (ext, mime) = value
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected EXT=TYPE, e.g. log=text/plain"))?;
+    let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+    let mime = mime.trim();
+    if ext.is_empty() || !mime.contains('/') || HeaderValue::from_str(mime).is_err() {
+        return Err(anyhow!("invalid MIME override: {}", value));
+    }
+    Ok((ext, mime.to_string()))
+}
@@ -0,0 +1,256 @@
+// 递归搜索文件名: /search?q=，支持子串、glob 和正则匹配，结果边遍历边流式返回
+use crate::auth::AuthUser;
+use crate::listing::{self, Entry, EntryKind};
+use crate::mime::MimeTypes;
+use crate::templates::{render_search_end, render_search_result, render_search_start};
+use crate::walk::{self, Source, Walker};
+use crate::AppState;
+use anyhow::{bail, Result};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::{future, stream, StreamExt};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::time::Instant;
+use tracing::{error, info};
+
+// 正则编译后的大小上限，避免过于复杂的表达式占用大量内存
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// 文件名匹配方式，均不区分大小写
+enum Matcher {
+    Substring(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    // ?mode=substring|glob|regex，未指定时含有 * ? [ 的按 glob 处理，否则按子串匹配
+    fn new(query: &str, mode: Option<&str>) -> Result<Self> {
+        let mode = mode.unwrap_or(if query.contains(['*', '?', '[']) { "glob" } else { "substring" });
+        Ok(match mode {
+            "substring" => Matcher::Substring(query.to_lowercase()),
+            "glob" => Matcher::Glob(GlobBuilder::new(query).case_insensitive(true).build()?.compile_matcher()),
+            "regex" => Matcher::Regex(
+                RegexBuilder::new(query)
+                    .case_insensitive(true)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()?,
+            ),
+            other => bail!("Unknown search mode: {}", other),
+        })
+    }
+
+    // 模式中含有 / 时匹配完整的相对路径，否则只匹配文件名
+    fn is_match(&self, query: &str, name: &str, path: &str) -> bool {
+        let target = if query.contains('/') { path } else { name };
+        match self {
+            Matcher::Substring(needle) => target.to_lowercase().contains(needle),
+            Matcher::Glob(glob) => glob.is_match(target),
+            Matcher::Regex(regex) => regex.is_match(target),
+        }
+    }
+}
+
+// 搜索提前结束的原因
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Stopped {
+    // 达到结果数上限
+    Limit,
+    Timeout,
+    Error,
+}
+
+enum Event {
+    Found(Entry),
+    Done { count: usize, stopped: Option<Stopped> },
+}
+
+// 一次搜索的遍历状态
+struct Search {
+    walker: Walker,
+    matcher: Matcher,
+    query: String,
+    // 来源本身不作为结果
+    sources: Vec<String>,
+    mime: MimeTypes,
+    limit: usize,
+    deadline: Instant,
+    count: usize,
+    finished: bool,
+}
+
+impl Search {
+    async fn next(&mut self) -> Option<Event> {
+        if self.finished {
+            return None;
+        }
+        if self.count >= self.limit {
+            return Some(self.finish(Some(Stopped::Limit)));
+        }
+        loop {
+            match tokio::time::timeout_at(self.deadline, self.walker.next()).await {
+                Err(_) => return Some(self.finish(Some(Stopped::Timeout))),
+                Ok(Err(e)) => {
+                    error!("Search failed: {:#}", e);
+                    return Some(self.finish(Some(Stopped::Error)));
+                }
+                Ok(Ok(None)) => return Some(self.finish(None)),
+                Ok(Ok(Some(entry))) => {
+                    if let Some(entry) = self.matched(entry) {
+                        self.count += 1;
+                        return Some(Event::Found(entry));
+                    }
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, stopped: Option<Stopped>) -> Event {
+        self.finished = true;
+        Event::Done { count: self.count, stopped }
+    }
+
+    fn matched(&self, entry: walk::Entry) -> Option<Entry> {
+        let (path, kind, size, modified) = match entry {
+            walk::Entry::Dir { name, modified } => (name, EntryKind::Dir, 0, modified),
+            walk::Entry::File { name, size, modified, .. } => (name, EntryKind::File, size, modified),
+        };
+        if self.sources.contains(&path) {
+            return None;
+        }
+        let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+        if !self.matcher.is_match(&self.query, &name, &path) {
+            return None;
+        }
+        Some(Entry {
+            mime: match kind {
+                EntryKind::Dir => None,
+                EntryKind::File => self.mime.by_extension(Path::new(&name)),
+            },
+            name,
+            path,
+            kind,
+            size,
+            mtime: modified.and_then(|t| u64::try_from(t.timestamp()).ok()),
+            files: None,
+        })
+    }
+}
+
+// 搜索文件和文件夹名称
+// 参数: q (搜索词)、mode、path (只搜索该目录)、limit (不超过 --search-limit)
+pub async fn search(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    let q = query.get("q").map(|q| q.trim()).unwrap_or_default().to_string();
+    if q.is_empty() {
+        return (StatusCode::BAD_REQUEST, "缺少搜索词").into_response();
+    }
+    let matcher = match Matcher::new(&q, query.get("mode").map(String::as_str)) {
+        Ok(matcher) => matcher,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("无效的搜索条件: {:#}", e)).into_response(),
+    };
+
+    // 未指定 path 时搜索所有共享目录，结果路径与 /files/ 后的路径相同
+    let scope = query.get("path").map(|p| p.trim_matches('/')).unwrap_or_default().to_string();
+    let sources = if !scope.is_empty() {
+        let dir = match state.checked_path(&scope, addr.ip()) {
+            Ok(dir) => dir,
+            Err(status) => return status.into_response(),
+        };
+        if !dir.is_dir() {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        vec![Source { path: dir, name: scope.clone() }]
+    } else if let [root] = state.roots.as_slice() {
+        vec![Source { path: root.path.clone(), name: String::new() }]
+    } else {
+        state
+            .roots
+            .iter()
+            .map(|root| Source { path: root.path.clone(), name: root.alias.clone() })
+            .collect()
+    };
+
+    let limit = query
+        .get("limit")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(state.search_limit)
+        .clamp(1, state.search_limit);
+    info!(ip = %addr.ip(), user = %user, "Search for {:?} in /{}", q, scope);
+
+    let search = Search {
+        walker: Walker::indexed(sources.clone(), &state),
+        matcher,
+        query: q.clone(),
+        sources: sources.into_iter().map(|s| s.name).collect(),
+        mime: state.mime.clone(),
+        limit,
+        deadline: Instant::now() + state.search_timeout,
+        count: 0,
+        finished: false,
+    };
+    let events = stream::unfold(search, |mut search| async move {
+        search.next().await.map(|event| (event, search))
+    });
+
+    let (content_type, body) = if listing::wants_json(&headers, &query) {
+        // {"query": ..., "path": ..., "results": [...], "count": N, "stopped": null|"limit"|"timeout"|"error"}
+        let start = format!(
+            r#"{{"query":{},"path":{},"results":["#,
+            serde_json::to_string(&q).unwrap_or_default(),
+            serde_json::to_string(&scope).unwrap_or_default()
+        );
+        let chunks = events.enumerate().map(|(i, event)| match event {
+            Event::Found(entry) => {
+                let separator = if i > 0 { "," } else { "" };
+                format!("{}{}", separator, serde_json::to_string(&entry).unwrap_or_default())
+            }
+            Event::Done { count, stopped } => format!(
+                r#"],"count":{},"stopped":{}}}"#,
+                count,
+                serde_json::to_string(&stopped).unwrap_or_default()
+            ),
+        });
+        ("application/json", stream_body(start, chunks))
+    } else {
+        let author = state.author.clone();
+        let start = render_search_start(&q, &scope, &author);
+        let chunks = events.map(move |event| match event {
+            Event::Found(entry) => render_search_result(&entry),
+            Event::Done { count, stopped } => render_search_end(&summary(count, stopped), &author),
+        });
+        ("text/html; charset=utf-8", stream_body(start, chunks))
+    };
+
+    ([(CONTENT_TYPE, content_type)], body).into_response()
+}
+
+fn stream_body(start: String, chunks: impl futures_util::Stream<Item = String> + Send + 'static) -> Body {
+    Body::from_stream(stream::once(future::ready(start)).chain(chunks).map(Ok::<_, Infallible>))
+}
+
+fn summary(count: usize, stopped: Option<Stopped>) -> String {
+    match stopped {
+        None if count == 0 => "没有找到匹配的文件".to_string(),
+        None => format!("共找到 {} 项", count),
+        Some(Stopped::Limit) => format!("结果过多，只显示前 {} 项，请使用更具体的搜索词", count),
+        Some(Stopped::Timeout) => format!("搜索超时，只显示已找到的 {} 项，请缩小搜索范围", count),
+        Some(Stopped::Error) => format!("搜索出错，只显示已找到的 {} 项", count),
+    }
+}
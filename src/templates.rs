@@ -1,14 +1,20 @@
 use crate::format_size;
+use crate::listing::{Entry, EntryKind, ListingOptions, Page, SortKey, View};
 use crate::Author;
 use crate::{PKG_DESCRIPTION, PKG_NAME, PKG_VERSION};
 use axum::response::{Html, IntoResponse, Response};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+// 链接中的路径段: RFC 3986 unreserved 之外的字符都需要百分号编码
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
 
 // 渲染文件列表页面
 pub fn render_file_list(
-    folders: Vec<(String, String, u64)>,
-    files: Vec<(String, String, u64)>,
+    page: &Page,
     current_path: Option<&str>,
+    options: &ListingOptions,
     author: &Author,
+    allow_upload: bool,
 ) -> Response {
     // 生成面包屑导航
     let mut breadcrumbs_html = String::from(r#"<a href="/" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">Home</a>"#);
@@ -21,41 +27,161 @@ pub fn render_file_list(
             let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
 
             for (idx, part) in parts.iter().enumerate() {
-                current.push_str("/");
-                current.push_str(part);
+                current.push('/');
+                current.push_str(&url_path(part));
 
                 let name = if idx == parts.len() - 1 {
                     // 最后一个部分，完整显示
                     part.to_string()
                 } else {
-                    if part.len() > 10 {
-                        format!("{}...", &part[0..10])
+                    // 按字符截断，中文等多字节字符不能按字节切分
+                    if part.chars().count() > 10 {
+                        format!("{}...", part.chars().take(10).collect::<String>())
                     } else {
                         part.to_string()
                     }
                 };
 
                 breadcrumbs_html
-                    .push_str(&format!(r#" / <a href="/files{}" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">{}</a>"#, current, name));
+                    .push_str(&format!(r#" / <a href="/files{}" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">{}</a>"#, current, escape_html(&name)));
             }
         }
     }
 
-    // 创建作者信息HTML
-    let author_html = format!(
-        r#"
-        <div class="flex items-center space-x-4">
-            <span class="text-slate-600 dark:text-slate-300">{}</span>
-            {}
-        </div>
-        "#,
-        author.name,
-        author.github.as_ref().map_or(String::new(), |github| {
-            format!(r#"<a href="{}" target="_blank" class="text-sky-600 hover:text-sky-700 dark:text-sky-400"><svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5 inline" viewBox="0 0 24 24" fill="currentColor"><path fill-rule="evenodd" clip-rule="evenodd" d="M12 2C6.477 2 2 6.477 2 12c0 4.42 2.865 8.164 6.839 9.489.5.092.682-.217.682-.482 0-.237-.008-.866-.013-1.7-2.782.603-3.369-1.341-3.369-1.341-.454-1.155-1.11-1.462-1.11-1.462-.908-.62.069-.608.069-.608 1.003.07 1.531 1.03 1.531 1.03.892 1.529 2.341 1.088 2.91.832.092-.647.35-1.088.636-1.338-2.22-.253-4.555-1.11-4.555-4.943 0-1.091.39-1.984 1.029-2.683-.103-.253-.446-1.27.098-2.647 0 0 .84-.269 2.75 1.025A9.578 9.578 0 0112 6.836c.85.004 1.705.114 2.504.336 1.909-1.294 2.747-1.025 2.747-1.025.546 1.377.202 2.394.1 2.647.64.699 1.028 1.592 1.028 2.683 0 3.842-2.339 4.687-4.566 4.935.359.309.678.919.678 1.852 0 1.336-.012 2.415-.012 2.743 0 .267.18.578.688.48C19.138 20.16 22 16.418 22 12c0-5.523-4.477-10-10-10z" /></svg></a>"#, github)
-        })
-    );
+    // 上传表单和拖拽区域，提交到当前目录
+    let upload_html = if allow_upload {
+        let action = match current_path {
+            Some(path) if path != "/" => format!("/files/{}", url_path(path)),
+            _ => "/".to_string(),
+        };
+        format!(
+            r#"<form id="upload-form" action="{action}" method="post" enctype="multipart/form-data" class="px-6 pt-6">
+                <div id="drop-zone" class="flex flex-col sm:flex-row items-center justify-center gap-3 p-6 rounded-lg border-2 border-dashed border-slate-300 dark:border-slate-600 text-slate-500 dark:text-slate-400 transition-colors">
+                    <span>拖拽文件到此处上传，或</span>
+                    <input type="file" name="file" multiple class="text-sm">
+                    <button type="submit" class="px-4 py-1 rounded bg-sky-600 hover:bg-sky-700 text-white text-sm">上传</button>
+                    <span id="upload-status" class="text-sm"></span>
+                </div>
+            </form>
+            <script>
+                (() => {{
+                    const form = document.getElementById('upload-form');
+                    const zone = document.getElementById('drop-zone');
+                    const status = document.getElementById('upload-status');
+                    const highlight = ['border-sky-500', 'bg-sky-50', 'dark:bg-slate-700/50'];
+                    ['dragenter', 'dragover'].forEach(type => zone.addEventListener(type, e => {{
+                        e.preventDefault();
+                        zone.classList.add(...highlight);
+                    }}));
+                    ['dragleave', 'drop'].forEach(type => zone.addEventListener(type, e => {{
+                        e.preventDefault();
+                        zone.classList.remove(...highlight);
+                    }}));
+                    zone.addEventListener('drop', async e => {{
+                        const data = new FormData();
+                        for (const file of e.dataTransfer.files) data.append('file', file, file.name);
+                        if (!data.has('file')) return;
+                        status.textContent = '上传中...';
+                        const res = await fetch(form.action, {{ method: 'POST', body: data }});
+                        if (res.ok) {{
+                            location.reload();
+                        }} else {{
+                            status.textContent = '上传失败: ' + res.status + ' ' + (await res.text());
+                        }}
+                    }});
+                }})();
+            </script>"#
+        )
+    } else {
+        String::new()
+    };
+
+    // 打包下载当前目录
+    let archive_url = match current_path {
+        Some(path) if path != "/" => format!("/archive/{}", url_path(path)),
+        _ => "/archive".to_string(),
+    };
+
+    // 多选打包下载工具栏，勾选框通过 form 属性关联到这个表单
+    let select_html = if page.entries.is_empty() {
+        String::new()
+    } else {
+        r#"<form id="select-form" action="/archive" method="post" class="flex items-center gap-3 mb-4 text-sm text-slate-600 dark:text-slate-300">
+                    <label class="flex items-center gap-1 cursor-pointer"><input type="checkbox" id="select-all"> 全选</label>
+                    <select name="format" class="rounded border border-slate-300 dark:border-slate-600 bg-white dark:bg-slate-700 px-2 py-1">
+                        <option value="zip">zip</option>
+                        <option value="tar.gz">tar.gz</option>
+                    </select>
+                    <button type="submit" id="select-submit" disabled class="px-3 py-1 rounded bg-sky-600 hover:bg-sky-700 text-white disabled:opacity-40 disabled:cursor-not-allowed">⬇️ 下载所选 (<span id="select-count">0</span>)</button>
+                </form>
+                <script>
+                    document.addEventListener('DOMContentLoaded', () => {
+                        const items = [...document.querySelectorAll('.select-item')];
+                        const all = document.getElementById('select-all');
+                        const submit = document.getElementById('select-submit');
+                        const count = document.getElementById('select-count');
+                        const update = () => {
+                            const n = items.filter(i => i.checked).length;
+                            count.textContent = n;
+                            submit.disabled = n === 0;
+                            all.checked = n === items.length;
+                        };
+                        items.forEach(i => i.addEventListener('change', update));
+                        all.addEventListener('change', () => {
+                            items.forEach(i => i.checked = all.checked);
+                            update();
+                        });
+                    });
+                </script>"#
+            .to_string()
+    };
+
+    let entries_html = match options.view {
+        View::Grid => grid_html(&page.entries),
+        View::Table => table_html(&page.entries),
+    };
 
     let html = format!(
+        r#"{header}
+        <div class="bg-white dark:bg-slate-800 rounded-xl shadow-md overflow-hidden border border-slate-100 dark:border-slate-700">
+            <div class="flex items-center justify-between gap-4 px-6 py-3 bg-sky-50 dark:bg-slate-750 border-b border-slate-200 dark:border-slate-700">
+                <div class="overflow-x-auto whitespace-nowrap">{breadcrumbs}</div>
+                <div class="shrink-0 flex items-center gap-3 whitespace-nowrap text-sm">
+                    {search_html}
+                    <a href="{archive_url}" class="px-3 py-1 rounded bg-sky-600 hover:bg-sky-700 text-white">📦 下载文件夹</a>
+                    <a href="{archive_url}?format=tar.gz" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">tar.gz</a>
+                </div>
+            </div>
+            {upload_html}
+            <div class="p-6">
+                <div class="flex flex-wrap items-start justify-between gap-x-4">
+                    {select_html}
+                    {sort_html}
+                </div>
+                {truncated_html}
+                {entries_html}
+                {pagination_html}
+            </div>
+{footer}"#,
+        header = page_header(author),
+        breadcrumbs = breadcrumbs_html,
+        search_html = search_box("", current_path.filter(|p| *p != "/").unwrap_or("")),
+        upload_html = upload_html,
+        archive_url = archive_url,
+        select_html = select_html,
+        sort_html = view_controls(options),
+        truncated_html = truncated_notice(page),
+        entries_html = entries_html,
+        pagination_html = pagination_nav(page, options),
+        footer = page_footer(author),
+    );
+
+    Html(html).into_response()
+}
+
+// 页面开头: HTML 头部和带作者信息的标题栏
+fn page_header(author: &Author) -> String {
+    format!(
         r#"<!DOCTYPE html>
 <html lang="zh-CN">
 <head>
@@ -81,22 +207,18 @@ pub fn render_file_list(
         <header class="flex justify-between items-center mb-6 pb-4 border-b border-slate-200 dark:border-slate-700">
             <h1 class="text-2xl font-semibold text-primary">{pkg_name} <span class="text-xs align-top bg-slate-100 dark:bg-slate-700 px-2 py-1 rounded">{pkg_version}</span></h1>
             {author_html}
-        </header>
-        
-        <div class="bg-white dark:bg-slate-800 rounded-xl shadow-md overflow-hidden border border-slate-100 dark:border-slate-700">
-            <div class="px-6 py-3 bg-sky-50 dark:bg-slate-750 border-b border-slate-200 dark:border-slate-700 overflow-x-auto whitespace-nowrap">
-                {breadcrumbs}
-            </div>
-            
-            <div class="p-6">
-                <div class="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 gap-4">
-                    {folders_html}
-                    {files_html}
-                    {empty_html}
-                </div>
-            </div>
-            
-            <div class="px-6 py-3 bg-sky-50 dark:bg-slate-750 border-t border-slate-200 dark:border-slate-700 text-center text-sm text-slate-500 dark:text-slate-400">
+        </header>"#,
+        author_html = author_html(author),
+        pkg_name = PKG_NAME,
+        pkg_version = PKG_VERSION,
+        pkg_description = PKG_DESCRIPTION
+    )
+}
+
+// 页面结尾: 版权信息和暗色模式脚本，同时关闭 page_header 之后打开的卡片
+fn page_footer(author: &Author) -> String {
+    format!(
+        r#"            <div class="px-6 py-3 bg-sky-50 dark:bg-slate-750 border-t border-slate-200 dark:border-slate-700 text-center text-sm text-slate-500 dark:text-slate-400">
                 &copy; {copyright_year} {author_name} • {pkg_description}
             </div>
         </div>
@@ -110,75 +232,407 @@ pub fn render_file_list(
     </script>
 </body>
 </html>"#,
-        author_html = author_html,
-        breadcrumbs = breadcrumbs_html,
-        folders_html = folders
-            .iter()
-            .map(|(name, path, _)| {
-                format!(
-                    r#"<a href="/files/{path}" class="flex items-center p-4 rounded-lg transition-colors hover:bg-sky-50 dark:hover:bg-slate-700/50 border border-transparent hover:border-sky-100 dark:hover:border-slate-600">
-                        <div class="mr-3 text-amber-500 dark:text-amber-400 text-xl">📁</div>
-                        <div class="flex-grow overflow-hidden">
-                            <div class="truncate font-medium">{name}</div>
-                            <div class="text-xs text-slate-500 dark:text-slate-400">目录</div>
-                        </div>
-                    </a>"#
-                )
-            })
-            .collect::<String>(),
-        files_html = files
-            .iter()
-            .map(|(name, path, size)| {
-                // 格式化文件大小
-                let size_str = format_size(*size);
-
-                // 文件图标选择
-                let icon = match name.split('.').last().unwrap_or("") {
-                    "pdf" => "📄",
-                    "doc" | "docx" => "📝",
-                    "xls" | "xlsx" => "📊",
-                    "ppt" | "pptx" => "📑",
-                    "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" => "🖼️",
-                    "mp3" | "wav" | "ogg" | "flac" => "🎵",
-                    "mp4" | "avi" | "mov" | "wmv" | "mkv" => "🎬",
-                    "zip" | "rar" | "7z" | "tar" | "gz" => "🗜️",
-                    "exe" | "msi" | "app" => "⚙️",
-                    "html" | "htm" => "🌐",
-                    "css" => "🎨",
-                    "js" | "ts" => "📜",
-                    "rs" | "go" | "py" | "java" | "c" | "cpp" | "cs" => "💻",
-                    "md" | "txt" => "📃",
-                    "json" | "xml" | "yaml" | "yml" => "🔧",
-                    "git" | "gitignore" => "📦",
-                    "apk" => "📱",
-                    "iso" => "💿",
-                    "torrent" => "🧲",
-                    "bak" | "old" | "temp" => "🗑️",
-                    _ => "📄",
-                };
-
-                format!(
-                    r#"<a href="/files/{path}" class="flex items-center p-4 rounded-lg transition-colors hover:bg-sky-50 dark:hover:bg-slate-700/50 border border-transparent hover:border-sky-100 dark:hover:border-slate-600">
-                        <div class="mr-3 text-sky-500 dark:text-sky-400 text-xl">{icon}</div>
-                        <div class="flex-grow overflow-hidden">
-                            <div class="truncate font-medium">{name}</div>
-                            <div class="text-xs text-slate-500 dark:text-slate-400">{size_str}</div>
-                        </div>
-                    </a>"#
-                )
-            })
-            .collect::<String>(),
-        empty_html = if folders.is_empty() && files.is_empty() {
-            r#"<div class="col-span-full py-12 text-center text-slate-500 dark:text-slate-400">此文件夹为空</div>"#
-        } else {
-            ""
-        },
         copyright_year = chrono::Local::now().format("%Y"),
         author_name = author.name,
-        pkg_name = PKG_NAME,
-        pkg_version = PKG_VERSION,
         pkg_description = PKG_DESCRIPTION
+    )
+}
+
+// 作者信息
+fn author_html(author: &Author) -> String {
+    format!(
+        r#"
+        <div class="flex items-center space-x-4">
+            <span class="text-slate-600 dark:text-slate-300">{}</span>
+            {}
+        </div>
+        "#,
+        author.name,
+        author.github.as_ref().map_or(String::new(), |github| {
+            format!(r#"<a href="{}" target="_blank" class="text-sky-600 hover:text-sky-700 dark:text-sky-400"><svg xmlns="http://www.w3.org/2000/svg" class="h-5 w-5 inline" viewBox="0 0 24 24" fill="currentColor"><path fill-rule="evenodd" clip-rule="evenodd" d="M12 2C6.477 2 2 6.477 2 12c0 4.42 2.865 8.164 6.839 9.489.5.092.682-.217.682-.482 0-.237-.008-.866-.013-1.7-2.782.603-3.369-1.341-3.369-1.341-.454-1.155-1.11-1.462-1.11-1.462-.908-.62.069-.608.069-.608 1.003.07 1.531 1.03 1.531 1.03.892 1.529 2.341 1.088 2.91.832.092-.647.35-1.088.636-1.338-2.22-.253-4.555-1.11-4.555-4.943 0-1.091.39-1.984 1.029-2.683-.103-.253-.446-1.27.098-2.647 0 0 .84-.269 2.75 1.025A9.578 9.578 0 0112 6.836c.85.004 1.705.114 2.504.336 1.909-1.294 2.747-1.025 2.747-1.025.546 1.377.202 2.394.1 2.647.64.699 1.028 1.592 1.028 2.683 0 3.842-2.339 4.687-4.566 4.935.359.309.678.919.678 1.852 0 1.336-.012 2.415-.012 2.743 0 .267.18.578.688.48C19.138 20.16 22 16.418 22 12c0-5.523-4.477-10-10-10z" /></svg></a>"#, github)
+        })
+    )
+}
+
+// 卡片视图
+fn grid_html(entries: &[Entry]) -> String {
+    let cards_html = entries
+        .iter()
+        .map(|entry| {
+            let Entry { size, mtime, .. } = entry;
+            let (name, href) = (escape_html(&entry.name), url_path(&entry.path));
+            if entry.kind == EntryKind::Dir {
+                return format!(
+                    r#"<div class="relative">
+                <a href="/files/{href}" class="flex items-center p-4 pr-10 rounded-lg transition-colors hover:bg-sky-50 dark:hover:bg-slate-700/50 border border-transparent hover:border-sky-100 dark:hover:border-slate-600">
+                    <div class="mr-3 text-amber-500 dark:text-amber-400 text-xl">📁</div>
+                    <div class="flex-grow overflow-hidden">
+                        <div class="truncate font-medium">{name}</div>
+                        <div class="text-xs text-slate-500 dark:text-slate-400">{summary}</div>
+                    </div>
+                </a>
+                {checkbox}
+                </div>"#,
+                    summary = folder_summary(entry),
+                    checkbox = select_checkbox(&entry.path, CARD_CHECKBOX)
+                );
+            }
+
+            // 格式化文件大小和修改时间
+            let size_str = match mtime.and_then(format_mtime) {
+                Some(modified) => format!("{} · {}", format_size(*size), modified),
+                None => format_size(*size),
+            };
+
+            let icon = file_icon(&entry.name);
+
+            format!(
+                r#"<div class="relative">
+                <a href="/files/{href}" class="flex items-center p-4 pr-28 rounded-lg transition-colors hover:bg-sky-50 dark:hover:bg-slate-700/50 border border-transparent hover:border-sky-100 dark:hover:border-slate-600">
+                    <div class="mr-3 text-sky-500 dark:text-sky-400 text-xl">{icon}</div>
+                    <div class="flex-grow overflow-hidden">
+                        <div class="truncate font-medium">{name}</div>
+                        <div class="text-xs text-slate-500 dark:text-slate-400">{size_str}</div>
+                    </div>
+                </a>
+                {actions}
+                {checkbox}
+                </div>"#,
+                actions = file_actions(&entry.path),
+                checkbox = select_checkbox(&entry.path, CARD_CHECKBOX)
+            )
+        })
+        .collect::<String>();
+    let empty_html = if entries.is_empty() {
+        r#"<div class="col-span-full py-12 text-center text-slate-500 dark:text-slate-400">此文件夹为空</div>"#
+    } else {
+        ""
+    };
+    format!(
+        r#"<div class="grid grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 gap-4">
+                    {cards_html}
+                    {empty_html}
+                </div>"#
+    )
+}
+
+// 详细列表视图: 名称、大小、修改时间、类型
+fn table_html(entries: &[Entry]) -> String {
+    if entries.is_empty() {
+        return r#"<div class="py-12 text-center text-slate-500 dark:text-slate-400">此文件夹为空</div>"#.to_string();
+    }
+
+    let rows: String = entries.iter().map(|entry| table_row(entry, false)).collect();
+
+    format!(
+        r#"<div class="overflow-x-auto">
+                <table class="w-full text-sm">
+                    {TABLE_HEAD}
+                    <tbody>
+                    {rows}
+                    </tbody>
+                </table>
+                </div>"#
+    )
+}
+
+const TABLE_HEAD: &str = r#"<thead class="text-left text-slate-500 dark:text-slate-400 border-b border-slate-200 dark:border-slate-700">
+                        <tr><th class="py-2 pl-2"></th><th class="py-2 pr-4 font-medium">名称</th><th class="py-2 pr-4 font-medium text-right">大小</th><th class="py-2 pr-4 font-medium">修改时间</th><th class="py-2 pr-4 font-medium">类型</th><th class="py-2 pr-2"></th></tr>
+                    </thead>"#;
+
+// 列表视图的一行; 搜索结果显示完整路径，没有勾选框
+fn table_row(entry: &Entry, search_result: bool) -> String {
+    let is_dir = entry.kind == EntryKind::Dir;
+    let (icon, size, kind, actions) = if is_dir {
+        let size = if entry.files.is_some() { format_size(entry.size) } else { "-".to_string() };
+        let kind = match entry.files {
+            Some(files) => format!("目录 ({} 个文件)", files),
+            None => "目录".to_string(),
+        };
+        ("📁", size, kind, String::new())
+    } else {
+        (
+            file_icon(&entry.name),
+            format_size(entry.size),
+            // 只显示类型本身，不显示 charset 等参数
+            entry
+                .mime
+                .as_deref()
+                .and_then(|mime| mime.split(';').next())
+                .unwrap_or("-")
+                .to_string(),
+            action_links(&entry.path),
+        )
+    };
+    let (checkbox, name) = if search_result {
+        (String::new(), &entry.path)
+    } else {
+        (select_checkbox(&entry.path, ""), &entry.name)
+    };
+    format!(
+        r#"<tr class="border-b border-slate-100 dark:border-slate-700 hover:bg-sky-50 dark:hover:bg-slate-700/50">
+                        <td class="py-2 pl-2 w-8">{checkbox}</td>
+                        <td class="py-2 pr-4 max-w-md truncate"><a href="/files/{href}" class="hover:text-sky-600 dark:hover:text-sky-400"><span class="mr-2">{icon}</span>{name}</a></td>
+                        <td class="py-2 pr-4 text-right whitespace-nowrap tabular-nums">{size}</td>
+                        <td class="py-2 pr-4 whitespace-nowrap tabular-nums">{modified}</td>
+                        <td class="py-2 pr-4 whitespace-nowrap text-slate-500 dark:text-slate-400">{kind}</td>
+                        <td class="py-2 pr-2 whitespace-nowrap text-xs space-x-2">{actions}</td>
+                    </tr>"#,
+        name = escape_html(name),
+        href = url_path(&entry.path),
+        modified = entry.mtime.and_then(format_mtime).unwrap_or_default(),
+    )
+}
+
+// 文件夹卡片的说明: 统计完成时显示总大小和文件数
+fn folder_summary(entry: &Entry) -> String {
+    match entry.files {
+        Some(files) => format!("目录 · {} · {} 个文件", format_size(entry.size), files),
+        None => "目录".to_string(),
+    }
+}
+
+// 搜索框，scope 为空时搜索所有共享目录，否则只搜索该目录
+fn search_box(query: &str, scope: &str) -> String {
+    let (placeholder, scope_input) = if scope.is_empty() {
+        ("搜索文件名", String::new())
+    } else {
+        ("在此文件夹中搜索", format!(r#"<input type="hidden" name="path" value="{}">"#, escape_html(scope)))
+    };
+    format!(
+        r#"<form action="/search" method="get" class="flex items-center">
+                        <input type="search" name="q" value="{}" placeholder="{}" class="w-40 sm:w-56 rounded border border-slate-300 dark:border-slate-600 bg-white dark:bg-slate-700 px-2 py-1">
+                        {}
+                    </form>"#,
+        escape_html(query),
+        placeholder,
+        scope_input
+    )
+}
+
+// 搜索结果页面按三段流式输出: 开头、每条结果一行、结尾的统计信息
+pub fn render_search_start(query: &str, scope: &str, author: &Author) -> String {
+    let scope_html = if scope.is_empty() {
+        String::new()
+    } else {
+        format!(
+            r#" / <a href="/files/{}" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">{}</a>"#,
+            url_path(scope),
+            escape_html(scope)
+        )
+    };
+    format!(
+        r#"{header}
+        <div class="bg-white dark:bg-slate-800 rounded-xl shadow-md overflow-hidden border border-slate-100 dark:border-slate-700">
+            <div class="flex items-center justify-between gap-4 px-6 py-3 bg-sky-50 dark:bg-slate-750 border-b border-slate-200 dark:border-slate-700">
+                <div class="overflow-x-auto whitespace-nowrap"><a href="/" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">Home</a>{scope_html} / 搜索 "{query}"</div>
+                <div class="shrink-0 whitespace-nowrap text-sm">{search_html}</div>
+            </div>
+            <div class="p-6">
+                <div class="overflow-x-auto">
+                <table class="w-full text-sm">
+                    {TABLE_HEAD}
+                    <tbody>
+"#,
+        header = page_header(author),
+        query = escape_html(query),
+        search_html = search_box(query, scope),
+    )
+}
+
+// 结果的名称和路径来自文件系统 (可能是上传的文件名)，由 table_row 转义
+pub fn render_search_result(entry: &Entry) -> String {
+    table_row(entry, true)
+}
+
+pub fn render_search_end(summary: &str, author: &Author) -> String {
+    format!(
+        r#"
+                    </tbody>
+                </table>
+                </div>
+                <div class="mt-6 text-center text-sm text-slate-500 dark:text-slate-400">{summary}</div>
+            </div>
+{footer}"#,
+        footer = page_footer(author),
+    )
+}
+
+// 转义文件名、路径和搜索词，上传的文件名同样是用户输入，避免其中的 HTML 被执行
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// 链接中的相对路径，逐段编码并保留分隔的 /
+fn url_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// 根据扩展名选择文件图标
+fn file_icon(name: &str) -> &'static str {
+    match name.split('.').next_back().unwrap_or("") {
+        "pdf" => "📄",
+        "doc" | "docx" => "📝",
+        "xls" | "xlsx" => "📊",
+        "ppt" | "pptx" => "📑",
+        "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" => "🖼️",
+        "mp3" | "wav" | "ogg" | "flac" => "🎵",
+        "mp4" | "avi" | "mov" | "wmv" | "mkv" => "🎬",
+        "zip" | "rar" | "7z" | "tar" | "gz" => "🗜️",
+        "exe" | "msi" | "app" => "⚙️",
+        "html" | "htm" => "🌐",
+        "css" => "🎨",
+        "js" | "ts" => "📜",
+        "rs" | "go" | "py" | "java" | "c" | "cpp" | "cs" => "💻",
+        "md" | "txt" => "📃",
+        "json" | "xml" | "yaml" | "yml" => "🔧",
+        "git" | "gitignore" => "📦",
+        "apk" => "📱",
+        "iso" => "💿",
+        "torrent" => "🧲",
+        "bak" | "old" | "temp" => "🗑️",
+        _ => "📄",
+    }
+}
+
+// 排序方式和视图切换: 点击当前排序列时切换升序/降序，切换视图时保留排序
+fn view_controls(options: &ListingOptions) -> String {
+    let sort = options.sort;
+    let links: String = SortKey::ALL
+        .iter()
+        .map(|&key| {
+            let label = match key {
+                SortKey::Name => "名称",
+                SortKey::Size => "大小",
+                SortKey::Mtime => "修改时间",
+            };
+            let active = key == sort.key;
+            // 当前列再次点击反转顺序；切换到大小和时间时默认降序，方便找最大/最新的文件
+            let descending = if active { !sort.descending } else { key != SortKey::Name };
+            let arrow = match (active, sort.descending) {
+                (false, _) => "",
+                (true, false) => " ↑",
+                (true, true) => " ↓",
+            };
+            let class = if active {
+                "font-medium text-sky-700 dark:text-sky-300"
+            } else {
+                "text-sky-600 hover:text-sky-700 dark:text-sky-400"
+            };
+            format!(
+                r#"<a href="?sort={}&order={}" class="{}">{}{}</a>"#,
+                key.as_str(),
+                if descending { "desc" } else { "asc" },
+                class,
+                label,
+                arrow
+            )
+        })
+        .collect();
+    let views: String = [(View::Grid, "卡片"), (View::Table, "列表")]
+        .iter()
+        .map(|&(view, label)| {
+            if view == options.view {
+                format!(r#"<span class="font-medium text-sky-700 dark:text-sky-300">{}</span>"#, label)
+            } else {
+                format!(
+                    r#"<a href="?view={}&sort={}&order={}" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">{}</a>"#,
+                    view.as_str(),
+                    sort.key.as_str(),
+                    if sort.descending { "desc" } else { "asc" },
+                    label
+                )
+            }
+        })
+        .collect();
+    format!(
+        r#"<div class="flex items-center gap-3 mb-4 text-sm text-slate-600 dark:text-slate-300">排序: {}<span class="text-slate-300 dark:text-slate-600">|</span>视图: {}</div>"#,
+        links, views
+    )
+}
+
+// 超过 --max-entries 时提示只显示了部分条目
+fn truncated_notice(page: &Page) -> String {
+    if !page.truncated {
+        return String::new();
+    }
+    format!(
+        r#"<div class="mb-4 px-4 py-2 rounded bg-amber-50 dark:bg-amber-900/30 text-sm text-amber-700 dark:text-amber-300">此文件夹条目过多，只列出了其中 {} 项，请缩小查找范围</div>"#,
+        page.total
+    )
+}
+
+// 上一页 / 下一页，下一页使用 cursor 以免翻页期间目录变化导致重复或遗漏
+fn pagination_nav(page: &Page, options: &ListingOptions) -> String {
+    if page.pages <= 1 {
+        return String::new();
+    }
+    let sort = options.sort;
+    let base = format!(
+        "sort={}&order={}&limit={}",
+        sort.key.as_str(),
+        if sort.descending { "desc" } else { "asc" },
+        page.limit
     );
+    let link = |href: String, label: &str| {
+        format!(r#"<a href="{}" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">{}</a>"#, href, label)
+    };
+    let prev = if page.page > 1 {
+        link(format!("?{}&page={}", base, page.page - 1), "← 上一页")
+    } else {
+        String::new()
+    };
+    let next = match &page.next_cursor {
+        Some(cursor) => link(
+            format!("?{}&page={}&cursor={}", base, page.page + 1, utf8_percent_encode(cursor, NON_ALPHANUMERIC)),
+            "下一页 →",
+        ),
+        None => String::new(),
+    };
+    format!(
+        r#"<div class="flex items-center justify-center gap-4 mt-6 text-sm text-slate-600 dark:text-slate-300">{}<span>第 {} / {} 页，共 {} 项</span>{}</div>"#,
+        prev, page.page, page.pages, page.total, next
+    )
+}
 
-    Html(html).into_response()
+// 修改时间按服务器本地时区显示
+fn format_mtime(mtime: u64) -> Option<String> {
+    let time = chrono::DateTime::from_timestamp(i64::try_from(mtime).ok()?, 0)?;
+    Some(time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+}
+
+// 文件卡片上的"打开" (浏览器内预览) 和"下载" (强制保存) 操作
+fn file_actions(path: &str) -> String {
+    format!(
+        r#"<div class="absolute top-1/2 right-10 -translate-y-1/2 flex gap-2 text-xs">
+                        {}
+                    </div>"#,
+        action_links(path)
+    )
+}
+
+fn action_links(path: &str) -> String {
+    let path = url_path(path);
+    format!(
+        r#"<a href="/files/{path}?inline=1" target="_blank" class="text-sky-600 hover:text-sky-700 dark:text-sky-400">打开</a>
+                        <a href="/files/{path}?download=1" download class="text-sky-600 hover:text-sky-700 dark:text-sky-400">下载</a>"#
+    )
+}
+
+// 卡片视图中勾选框位于卡片右侧
+const CARD_CHECKBOX: &str = "absolute top-1/2 right-3 -translate-y-1/2";
+
+// 多选勾选框，extra_class 控制位置
+fn select_checkbox(path: &str, extra_class: &str) -> String {
+    let path = escape_html(path);
+    format!(
+        r#"<input type="checkbox" name="path" value="{path}" form="select-form" class="select-item {extra_class} h-4 w-4 cursor-pointer accent-sky-600">"#
+    )
 }
@@ -0,0 +1,430 @@
+// 可断点续传的上传 (tus 1.0 协议的核心部分)
+//
+// POST   /uploads/{dir}  创建上传, 需要 Upload-Length 和 Upload-Metadata: filename <base64>
+// HEAD   /uploads/{id}   查询已接收的字节数 (Upload-Offset)
+// PATCH  /uploads/{id}   从 Upload-Offset 处继续写入
+// DELETE /uploads/{id}   放弃上传
+//
+// 未完成的数据和元信息保存在根目录下的 .share_these/uploads 中，服务重启后仍可继续
+// 该目录始终被 Filter 排除，即使 --hidden 也无法通过 /files/ 访问
+use crate::auth::{generate_token, AuthUser};
+use crate::filter;
+use crate::upload::sanitize_file_name;
+use crate::AppState;
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, LOCATION},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::StreamExt;
+use std::net::SocketAddr;
+use std::path::{Path as FsPath, PathBuf};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+
+// 未完成的上传在状态目录中的位置
+const UPLOADS_DIR: &str = "uploads";
+
+fn state_dir(root: &FsPath) -> PathBuf {
+    root.join(filter::STATE_DIR).join(UPLOADS_DIR)
+}
+
+// 未完成上传的元信息，保存在 <id>.info 中
+struct UploadInfo {
+    target: PathBuf,
+    length: u64,
+}
+
+impl UploadInfo {
+    fn encode(&self) -> String {
+        format!("target={}\nlength={}\n", self.target.display(), self.length)
+    }
+
+    fn decode(content: &str) -> Result<Self> {
+        let mut target = None;
+        let mut length = None;
+        for line in content.lines() {
+            match line.split_once('=') {
+                Some(("target", value)) => target = Some(PathBuf::from(value)),
+                Some(("length", value)) => length = Some(value.parse()?),
+                _ => {}
+            }
+        }
+        Ok(Self {
+            target: target.ok_or_else(|| anyhow!("Missing target in upload info"))?,
+            length: length.ok_or_else(|| anyhow!("Missing length in upload info"))?,
+        })
+    }
+}
+
+// 一个未完成上传在磁盘上的位置
+struct PendingUpload {
+    info_path: PathBuf,
+    data_path: PathBuf,
+}
+
+impl PendingUpload {
+    fn new(state_dir: &FsPath, id: &str) -> Self {
+        Self {
+            info_path: state_dir.join(format!("{}.info", id)),
+            data_path: state_dir.join(format!("{}.part", id)),
+        }
+    }
+
+    // 在所有根目录中查找上传记录
+    fn find(state: &AppState, id: &str) -> Option<Self> {
+        if !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        state
+            .roots
+            .iter()
+            .map(|root| Self::new(&state_dir(&root.path), id))
+            .find(|upload| upload.info_path.is_file())
+    }
+
+    async fn info(&self) -> Result<UploadInfo> {
+        let content = fs::read_to_string(&self.info_path)
+            .await
+            .with_context(|| format!("Failed to read upload info {:?}", self.info_path))?;
+        UploadInfo::decode(&content)
+    }
+
+    // 已接收的字节数即数据文件的长度
+    async fn offset(&self) -> Result<u64> {
+        let metadata = fs::metadata(&self.data_path)
+            .await
+            .with_context(|| format!("Failed to get metadata for {:?}", self.data_path))?;
+        Ok(metadata.len())
+    }
+
+    async fn remove(&self) {
+        let _ = fs::remove_file(&self.data_path).await;
+        let _ = fs::remove_file(&self.info_path).await;
+    }
+}
+
+// 同一个上传同时只允许一个 PATCH 写入
+struct UploadLock<'a> {
+    state: &'a AppState,
+    id: String,
+}
+
+impl<'a> UploadLock<'a> {
+    fn acquire(state: &'a AppState, id: &str) -> Option<Self> {
+        let mut active = state.active_uploads.lock().unwrap();
+        active.insert(id.to_string()).then(|| Self { state, id: id.to_string() })
+    }
+}
+
+impl Drop for UploadLock<'_> {
+    fn drop(&mut self) {
+        self.state.active_uploads.lock().unwrap().remove(&self.id);
+    }
+}
+
+fn tus_response(status: StatusCode) -> Response {
+    let mut response = status.into_response();
+    response
+        .headers_mut()
+        .insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+// 在根目录创建上传 (仅单个根目录时有效)
+pub async fn create_root(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Response {
+    create_upload(String::new(), addr, state, user, headers).await
+}
+
+// 在指定目录创建上传
+pub async fn create(
+    Path(path): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Response {
+    create_upload(path, addr, state, user, headers).await
+}
+
+async fn create_upload(
+    path: String,
+    addr: SocketAddr,
+    state: AppState,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Response {
+    let dir = match state.checked_path(&path, addr.ip()) {
+        Ok(dir) => dir,
+        Err(status) => return tus_response(status),
+    };
+    if !dir.is_dir() {
+        error!(ip = %addr.ip(), user = %user, "Upload target is not a directory: {:?}", dir);
+        return tus_response(StatusCode::NOT_FOUND);
+    }
+
+    let Some(length) = headers
+        .get(UPLOAD_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    else {
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+    if length > state.upload_limit {
+        error!(ip = %addr.ip(), user = %user, "Upload too large: {} bytes", length);
+        return tus_response(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let Some(file_name) = headers
+        .get(UPLOAD_METADATA)
+        .and_then(|v| v.to_str().ok())
+        .and_then(metadata_file_name)
+    else {
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+    let Some(file_name) = sanitize_file_name(&file_name).filter(|n| !n.is_empty()) else {
+        error!(ip = %addr.ip(), user = %user, "安全问题: 非法的上传文件名: {}", file_name);
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+
+    let target = dir.join(file_name);
+    // 不允许上传会被过滤规则隐藏的文件
+    if !state.filter.permits(&target, false) {
+        error!(ip = %addr.ip(), user = %user, "Upload target is filtered: {:?}", target);
+        return tus_response(StatusCode::FORBIDDEN);
+    }
+    if target.exists() {
+        error!(ip = %addr.ip(), user = %user, "Upload target already exists: {:?}", target);
+        return tus_response(StatusCode::CONFLICT);
+    }
+
+    // 上传记录保存在目标目录所属的根目录下，完成时 rename 不会跨文件系统
+    let Some(root) = state.roots.iter().find(|root| dir.starts_with(&root.path)) else {
+        return tus_response(StatusCode::NOT_FOUND);
+    };
+    let id = generate_token();
+    let upload = PendingUpload::new(&state_dir(&root.path), &id);
+    let info = UploadInfo { target, length };
+
+    if let Err(e) = init_upload(&upload, &info).await {
+        error!(ip = %addr.ip(), user = %user, "Failed to create upload: {:#}", e);
+        upload.remove().await;
+        return tus_response(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    info!(ip = %addr.ip(), user = %user, "Resumable upload created: {} -> {:?} ({} bytes)", id, info.target, length);
+
+    let mut response = tus_response(StatusCode::CREATED);
+    let headers = response.headers_mut();
+    headers.insert(LOCATION, format!("/uploads/{}", id).parse().unwrap());
+    headers.insert(UPLOAD_OFFSET, HeaderValue::from_static("0"));
+    response
+}
+
+async fn init_upload(upload: &PendingUpload, info: &UploadInfo) -> Result<()> {
+    if let Some(dir) = upload.info_path.parent() {
+        fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("Failed to create upload state directory {:?}", dir))?;
+    }
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&upload.data_path)
+        .await
+        .with_context(|| format!("Failed to create upload data {:?}", upload.data_path))?;
+    fs::write(&upload.info_path, info.encode())
+        .await
+        .with_context(|| format!("Failed to write upload info {:?}", upload.info_path))?;
+    Ok(())
+}
+
+// 从 Upload-Metadata 中取出文件名 (格式: key base64,key base64)
+fn metadata_file_name(metadata: &str) -> Option<String> {
+    metadata
+        .split(',')
+        .filter_map(|pair| pair.trim().split_once(' '))
+        .find(|(key, _)| *key == "filename" || *key == "name")
+        .and_then(|(_, value)| STANDARD.decode(value.trim()).ok())
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+// 查询上传进度
+pub async fn status(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    let Some(upload) = PendingUpload::find(&state, &id) else {
+        return tus_response(StatusCode::NOT_FOUND);
+    };
+
+    let (info, offset) = match (upload.info().await, upload.offset().await) {
+        (Ok(info), Ok(offset)) => (info, offset),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Failed to read upload {}: {:#}", id, e);
+            return tus_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut response = tus_response(StatusCode::OK);
+    let headers = response.headers_mut();
+    headers.insert(UPLOAD_OFFSET, offset.into());
+    headers.insert(UPLOAD_LENGTH, info.length.into());
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+// 从指定偏移继续写入
+pub async fn append(
+    Path(id): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    if headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) != Some("application/offset+octet-stream") {
+        return tus_response(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+    let Some(client_offset) = headers
+        .get(UPLOAD_OFFSET)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    else {
+        return tus_response(StatusCode::BAD_REQUEST);
+    };
+
+    let Some(upload) = PendingUpload::find(&state, &id) else {
+        return tus_response(StatusCode::NOT_FOUND);
+    };
+    let Some(_lock) = UploadLock::acquire(&state, &id) else {
+        return tus_response(StatusCode::LOCKED);
+    };
+
+    let (info, offset) = match (upload.info().await, upload.offset().await) {
+        (Ok(info), Ok(offset)) => (info, offset),
+        (Err(e), _) | (_, Err(e)) => {
+            error!(ip = %addr.ip(), user = %user, "Failed to read upload {}: {:#}", id, e);
+            return tus_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if client_offset != offset {
+        return tus_response(StatusCode::CONFLICT);
+    }
+    // 声明的请求体长度超过剩余长度时直接拒绝，不写入任何数据
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|n| n > info.length - offset) {
+        error!(ip = %addr.ip(), user = %user, "Upload {} body exceeds Upload-Length", id);
+        let mut response = tus_response(StatusCode::PAYLOAD_TOO_LARGE);
+        response.headers_mut().insert(UPLOAD_OFFSET, offset.into());
+        return response;
+    }
+
+    let (offset, result) = write_body(&upload.data_path, body, offset, info.length).await;
+    if let Err(e) = &result {
+        error!(ip = %addr.ip(), user = %user, "Upload {} interrupted at {} bytes: {:#}", id, offset, e);
+    }
+
+    // 超出长度的请求整体视为失败，不完成上传，客户端可从返回的 Upload-Offset 重试
+    let overflowed = matches!(&result, Err(e) if e.is::<UploadOverflow>());
+    if offset == info.length && !overflowed {
+        if info.target.exists() {
+            error!(ip = %addr.ip(), user = %user, "Upload target already exists: {:?}", info.target);
+            return tus_response(StatusCode::CONFLICT);
+        }
+        if let Err(e) = fs::rename(&upload.data_path, &info.target).await {
+            error!(ip = %addr.ip(), user = %user, "Failed to finish upload {}: {}", id, e);
+            return tus_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        let _ = fs::remove_file(&upload.info_path).await;
+        info!(ip = %addr.ip(), user = %user, "File uploaded: {:?} ({} bytes)", info.target, info.length);
+    }
+
+    let mut response = tus_response(match result {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(e) if e.is::<UploadOverflow>() => StatusCode::PAYLOAD_TOO_LARGE,
+        Err(_) => StatusCode::BAD_REQUEST,
+    });
+    response.headers_mut().insert(UPLOAD_OFFSET, offset.into());
+    response
+}
+
+// 请求体超过了 Upload-Length
+#[derive(Debug)]
+struct UploadOverflow;
+
+impl std::fmt::Display for UploadOverflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("request body exceeds Upload-Length")
+    }
+}
+
+impl std::error::Error for UploadOverflow {}
+
+// 追加写入请求体，返回写入后的偏移；中途出错时已写入的数据保留，供客户端续传
+async fn write_body(data_path: &FsPath, body: Body, mut offset: u64, length: u64) -> (u64, Result<()>) {
+    let mut file = match OpenOptions::new().append(true).open(data_path).await {
+        Ok(file) => file,
+        Err(e) => return (offset, Err(e.into())),
+    };
+
+    let mut stream = body.into_data_stream();
+    let result = async {
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.context("Failed to read request body")?;
+            // 超出部分所在的数据块整块丢弃，不写入 Upload-Length 之后的数据
+            if chunk.len() as u64 > length - offset {
+                return Err(UploadOverflow.into());
+            }
+            file.write_all(&chunk).await?;
+            offset += chunk.len() as u64;
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = file.sync_all().await {
+        return (offset, Err(e.into()));
+    }
+    (offset, result)
+}
+
+// 放弃上传并删除已接收的数据
+pub async fn terminate(
+    Path(id): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Response {
+    let Some(upload) = PendingUpload::find(&state, &id) else {
+        return tus_response(StatusCode::NOT_FOUND);
+    };
+    let Some(_lock) = UploadLock::acquire(&state, &id) else {
+        return tus_response(StatusCode::LOCKED);
+    };
+
+    upload.remove().await;
+    info!(ip = %addr.ip(), user = %user, "Resumable upload terminated: {}", id);
+    tus_response(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,184 @@
+use crate::auth::AuthUser;
+use crate::AppState;
+use anyhow::{Context, Result};
+use axum::{
+    extract::{multipart::Field, ConnectInfo, Multipart, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+};
+use std::net::SocketAddr;
+use std::path::{Path as FsPath, PathBuf};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info};
+
+// 上传到根目录 (仅单个根目录时有效)
+pub async fn upload_root(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    multipart: Multipart,
+) -> Response {
+    upload_into(String::new(), addr, state, user, multipart).await
+}
+
+// 上传到指定目录
+pub async fn upload_files(
+    Path(path): Path<String>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    user: AuthUser,
+    multipart: Multipart,
+) -> Response {
+    upload_into(path, addr, state, user, multipart).await
+}
+
+// 将 multipart 中的每个文件流式写入目标目录
+// 先写入同目录下的临时文件，完成后原子重命名，避免其他人下载到写了一半的文件
+async fn upload_into(
+    path: String,
+    addr: SocketAddr,
+    state: AppState,
+    user: AuthUser,
+    mut multipart: Multipart,
+) -> Response {
+    let dir = match state.checked_path(&path, addr.ip()) {
+        Ok(dir) => dir,
+        Err(status) => return status.into_response(),
+    };
+
+    if !dir.is_dir() {
+        error!(ip = %addr.ip(), user = %user, "Upload target is not a directory: {:?}", dir);
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                error!(ip = %addr.ip(), user = %user, "Invalid multipart body: {}", e);
+                return e.status().into_response();
+            }
+        };
+
+        // 忽略非文件字段
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        if file_name.is_empty() {
+            continue;
+        }
+
+        let Some(file_name) = sanitize_file_name(&file_name) else {
+            error!(ip = %addr.ip(), user = %user, "安全问题: 非法的上传文件名: {}", file_name);
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+
+        let target = dir.join(file_name);
+        // 不允许上传会被过滤规则隐藏的文件
+        if !state.filter.permits(&target, false) {
+            error!(ip = %addr.ip(), user = %user, "Upload target is filtered: {:?}", target);
+            return StatusCode::FORBIDDEN.into_response();
+        }
+        if target.exists() {
+            error!(ip = %addr.ip(), user = %user, "Upload target already exists: {:?}", target);
+            return (StatusCode::CONFLICT, format!("{} 已存在", file_name)).into_response();
+        }
+
+        match save_field(field, &target).await {
+            Ok(size) => {
+                info!(ip = %addr.ip(), user = %user, "File uploaded: {:?} ({} bytes)", target, size);
+            }
+            Err(UploadError::Multipart(status, e)) => {
+                error!(ip = %addr.ip(), user = %user, "Upload aborted: {:?}, error: {}", target, e);
+                return status.into_response();
+            }
+            Err(UploadError::Io(e)) => {
+                error!(ip = %addr.ip(), user = %user, "Failed to save upload: {:?}, error: {:#}", target, e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    // 上传完成后回到目录页面
+    let location = if path.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/files/{}", path)
+    };
+    Redirect::to(&location).into_response()
+}
+
+enum UploadError {
+    // 客户端中断或超过大小限制
+    Multipart(StatusCode, String),
+    Io(anyhow::Error),
+}
+
+// 流式写入单个文件字段，返回写入的字节数
+async fn save_field(mut field: Field<'_>, target: &FsPath) -> Result<u64, UploadError> {
+    let temp = temp_path(target);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp)
+        .await
+        .with_context(|| format!("Failed to create temp file {:?}", temp))
+        .map_err(UploadError::Io)?;
+
+    let result = write_chunks(&mut field, &mut file, &temp).await;
+    drop(file);
+
+    match result {
+        Ok(size) => {
+            fs::rename(&temp, target)
+                .await
+                .with_context(|| format!("Failed to rename {:?} to {:?}", temp, target))
+                .map_err(UploadError::Io)?;
+            Ok(size)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp).await;
+            Err(e)
+        }
+    }
+}
+
+async fn write_chunks(field: &mut Field<'_>, file: &mut File, temp: &FsPath) -> Result<u64, UploadError> {
+    let mut size = 0u64;
+    loop {
+        match field.chunk().await {
+            Ok(Some(chunk)) => {
+                file.write_all(&chunk)
+                    .await
+                    .with_context(|| format!("Failed to write temp file {:?}", temp))
+                    .map_err(UploadError::Io)?;
+                size += chunk.len() as u64;
+            }
+            Ok(None) => break,
+            Err(e) => return Err(UploadError::Multipart(e.status(), e.body_text())),
+        }
+    }
+
+    file.sync_all()
+        .await
+        .with_context(|| format!("Failed to sync temp file {:?}", temp))
+        .map_err(UploadError::Io)?;
+    Ok(size)
+}
+
+// 上传中的临时文件: 同目录下的隐藏文件，保证 rename 不跨文件系统
+fn temp_path(target: &FsPath) -> PathBuf {
+    let name = target.file_name().and_then(|n| n.to_str()).unwrap_or("upload");
+    target.with_file_name(format!(".{}.{}.part", name, crate::auth::generate_token()))
+}
+
+// 上传文件名只能是单个路径段，不允许携带目录和控制字符
+// 也不允许以 . 开头: 点文件包括 .shareignore / .gitignore，上传它们可以改写分享者的过滤规则
+pub fn sanitize_file_name(name: &str) -> Option<&str> {
+    if name.contains(['/', '\\']) || name.chars().any(char::is_control) || name.starts_with('.') {
+        return None;
+    }
+    Some(name)
+}
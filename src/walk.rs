@@ -0,0 +1,154 @@
+// 递归遍历共享目录，供打包下载和搜索使用，与目录列表使用同一套过滤和符号链接规则
+use crate::filter::Filter;
+use crate::index::{Index, Node};
+use crate::jail::Jail;
+use crate::AppState;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::warn;
+
+// 遍历来源: 磁盘路径 + 名称 (打包时为压缩包中的名称，搜索时为 /files/ 后的路径)，目录会被递归遍历
+#[derive(Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub name: String,
+}
+
+// 遍历到的一个条目，name 为来源名称加上相对路径
+pub enum Entry {
+    Dir { name: String, modified: Option<DateTime<Utc>> },
+    File { path: PathBuf, name: String, size: u64, modified: Option<DateTime<Utc>> },
+}
+
+// 深度优先遍历所有来源；除来源本身外，符号链接指向的目录不会被递归，避免循环
+// 无法读取的条目 (如失效的符号链接) 不影响整个遍历
+// 不符合符号链接策略的符号链接以及隐藏、被排除的条目直接跳过
+// 待遍历的条目: (磁盘路径, 名称, 是否为来源本身, 索引中的条目)
+type Pending = (PathBuf, String, bool, Option<Node>);
+
+pub struct Walker {
+    stack: Vec<Pending>,
+    jail: Jail,
+    filter: Filter,
+    // 为 Some 时优先从索引读取目录内容
+    index: Option<Index>,
+}
+
+impl Walker {
+    // 直接读取磁盘，打包下载使用，保证内容是最新的
+    pub fn new(sources: Vec<Source>, state: &AppState) -> Self {
+        Self {
+            stack: sources.into_iter().rev().map(|s| (s.path, s.name, true, None)).collect(),
+            jail: state.jail.clone(),
+            filter: state.filter.clone(),
+            index: None,
+        }
+    }
+
+    // 索引就绪时从索引读取，未启用或未就绪时读取磁盘
+    pub fn indexed(sources: Vec<Source>, state: &AppState) -> Self {
+        Self { index: state.index.clone(), ..Self::new(sources, state) }
+    }
+
+    pub async fn next(&mut self) -> Result<Option<Entry>> {
+        let (path, name, is_source, node) = loop {
+            let Some((path, name, is_source, node)) = self.stack.pop() else {
+                return Ok(None);
+            };
+            // 索引中的符号链接仍需读取磁盘以跟随链接
+            if let Some(node) = node.filter(|node| !node.symlink) {
+                break (path, name, is_source, node);
+            }
+            match fs::metadata(&path).await {
+                Ok(metadata) => {
+                    let is_symlink = fs::symlink_metadata(&path)
+                        .await
+                        .map(|m| m.file_type().is_symlink())
+                        .unwrap_or(false);
+                    let node = Node { symlink: is_symlink, ..Node::new(Default::default(), &metadata) };
+                    break (path, name, is_source, node);
+                }
+                Err(e) if !is_source => warn!("Skipping {:?}: {}", path, e),
+                Err(e) => return Err(e).with_context(|| format!("Failed to get metadata for {:?}", path)),
+            }
+        };
+        let modified = node.modified.map(DateTime::<Utc>::from);
+
+        if !node.is_dir {
+            return Ok(Some(Entry::File { path, name, size: node.size, modified }));
+        }
+
+        if is_source || !node.symlink {
+            let mut children = match self.index.as_ref().and_then(|index| index.children(&path)) {
+                Some(nodes) => self.indexed_children(&path, &name, &nodes).await,
+                None => self.disk_children(&path, &name).await?,
+            };
+            // 逆序入栈，出栈时按名称顺序
+            children.sort_by(|a, b| b.1.cmp(&a.1));
+            self.stack.extend(children);
+        }
+
+        Ok(Some(Entry::Dir { name, modified }))
+    }
+
+    async fn disk_children(&self, path: &Path, name: &str) -> Result<Vec<Pending>> {
+        let mut children = Vec::new();
+        let dir_filter = self.filter.dir(path);
+        let mut entries = fs::read_dir(path)
+            .await
+            .with_context(|| format!("Failed to read directory {:?}", path))?;
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("Failed to read directory entry in {:?}", path))?
+        {
+            let child = entry.path();
+            if entry.file_type().await.is_ok_and(|t| t.is_symlink()) && !self.jail.permits(&child) {
+                warn!("Skipping symlink {:?}", child);
+                continue;
+            }
+            let is_dir = fs::metadata(&child).await.is_ok_and(|m| m.is_dir());
+            if !dir_filter.permits(&entry.file_name(), is_dir) {
+                continue;
+            }
+            children.push((child, child_name(name, &entry.file_name()), false, None));
+        }
+        Ok(children)
+    }
+
+    // 与 disk_children 相同的规则，只有符号链接需要读取磁盘
+    async fn indexed_children(&self, path: &Path, name: &str, nodes: &[Node]) -> Vec<Pending> {
+        let mut children = Vec::new();
+        let dir_filter = self.filter.dir(path);
+        for node in nodes {
+            let child = path.join(&node.name);
+            if node.symlink && !self.jail.permits(&child) {
+                warn!("Skipping symlink {:?}", child);
+                continue;
+            }
+            let is_dir = if node.symlink {
+                fs::metadata(&child).await.is_ok_and(|m| m.is_dir())
+            } else {
+                node.is_dir
+            };
+            if !dir_filter.permits(&node.name, is_dir) {
+                continue;
+            }
+            children.push((child, child_name(name, &node.name), false, Some(node.clone())));
+        }
+        children
+    }
+}
+
+// 来源名称为空时直接使用相对路径
+fn child_name(parent: &str, name: &OsStr) -> String {
+    let name = name.to_string_lossy();
+    if parent.is_empty() {
+        name.into_owned()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
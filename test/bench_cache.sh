@@ -0,0 +1,18 @@
+# benchmarking cache hits: many concurrent downloads of the same small file
+# needs oha: cargo install oha
+
+# 1MB file, still small enough to be cached (--cache-max-file-size defaults to 1M)
+mkdir -p /tmp/share_these_bench
+head -c 1048576 /dev/urandom > /tmp/share_these_bench/small.bin
+
+# my rust app
+cargo build --release
+./target/release/share_these -p 3000 /tmp/share_these_bench > /dev/null &
+server=$!
+sleep 1
+
+# warm up the cache, then measure hits only
+curl -s -o /dev/null http://localhost:3000/files/small.bin
+oha --no-tui -z 10s -c 64 http://localhost:3000/files/small.bin
+
+kill $server